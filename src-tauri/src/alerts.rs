@@ -0,0 +1,307 @@
+//! Raises the alerts set up in the config file, so trouble gets noticed even when the widget is
+//! covered up.
+
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use serde::Serialize;
+use tauri::{api::notification::Notification, AppHandle, Manager, State};
+
+use crate::config::{AlertCondition, AlertRule};
+use crate::data_harvester::{unix_time_millis, Data};
+use crate::metrics::{self, Sample};
+use crate::utils::logging::log_at;
+
+/// How many of the latest alert events are kept for [`get_alert_history`].
+const HISTORY_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertEventKind {
+    Firing,
+    Resolved,
+}
+
+/// A series whose value met a condition.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breach {
+    /// Like `toerings_temperature_celsius{sensor="k10temp"}`.
+    pub series: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AlertEvent {
+    pub rule: String,
+    pub kind: AlertEventKind,
+    /// In milliseconds since the Unix epoch.
+    pub time: u64,
+    /// What made the alert fire. Empty when it's resolved.
+    pub breaches: Vec<Breach>,
+}
+
+impl AlertEvent {
+    fn summary(&self) -> String {
+        self.breaches
+            .iter()
+            .map(|breach| format!("{} is {}", breach.series, breach.value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl AlertCondition {
+    fn matches(&self, sample: &Sample) -> bool {
+        sample.family.sample_name() == self.metric
+            && self.labels.iter().all(|(name, value)| {
+                sample
+                    .labels
+                    .iter()
+                    .any(|(label, label_value)| label == name && label_value == value)
+            })
+    }
+
+    /// Whether `value` is past the threshold or, once the alert is firing, still short of the
+    /// level at which it clears.
+    fn is_met(&self, value: f64, firing: bool) -> bool {
+        match (self.above, self.below) {
+            (Some(above), _) => {
+                let threshold = if firing {
+                    self.clear.unwrap_or(above)
+                } else {
+                    above
+                };
+                value > threshold
+            }
+            (None, Some(below)) => {
+                let threshold = if firing {
+                    self.clear.unwrap_or(below)
+                } else {
+                    below
+                };
+                value < threshold
+            }
+            (None, None) => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RuleState {
+    rule: AlertRule,
+    /// When the conditions started holding, if they do.
+    holding_since: Option<u64>,
+    firing: bool,
+    last_fired: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Alerts {
+    rules: Vec<RuleState>,
+    history: VecDeque<AlertEvent>,
+}
+
+impl Alerts {
+    /// Replaces the rules. Rules that haven't changed carry on where they were, so editing one rule
+    /// doesn't fire all the others again.
+    pub fn set_rules(&mut self, rules: &[AlertRule]) {
+        let mut previous = std::mem::take(&mut self.rules);
+        self.rules = rules
+            .iter()
+            .map(
+                |rule| match previous.iter().position(|state| state.rule == *rule) {
+                    Some(i) => previous.swap_remove(i),
+                    None => RuleState {
+                        rule: rule.clone(),
+                        holding_since: None,
+                        firing: false,
+                        last_fired: None,
+                    },
+                },
+            )
+            .collect();
+    }
+
+    /// Checks the rules against `samples`, returning the alerts that fired or were resolved.
+    pub fn evaluate(&mut self, samples: &[Sample], now: u64) -> Vec<AlertEvent> {
+        let mut events = Vec::new();
+        for state in &mut self.rules {
+            let mut breaches = Vec::new();
+            let holds = state.rule.when.iter().all(|condition| {
+                let before = breaches.len();
+                breaches.extend(
+                    samples
+                        .iter()
+                        .filter(|sample| {
+                            condition.matches(sample)
+                                && condition.is_met(sample.value, state.firing)
+                        })
+                        .map(|sample| Breach {
+                            series: sample.id(),
+                            value: sample.value,
+                        }),
+                );
+                breaches.len() > before
+            });
+
+            if !holds {
+                state.holding_since = None;
+                if state.firing {
+                    state.firing = false;
+                    events.push(AlertEvent {
+                        rule: state.rule.name.clone(),
+                        kind: AlertEventKind::Resolved,
+                        time: now,
+                        breaches: Vec::new(),
+                    });
+                }
+                continue;
+            }
+            if state.firing {
+                continue;
+            }
+
+            let holding_since = *state.holding_since.get_or_insert(now);
+            let held_for = Duration::from_millis(now.saturating_sub(holding_since));
+            let cooling_down = state.last_fired.map_or(false, |last_fired| {
+                Duration::from_millis(now.saturating_sub(last_fired)) < state.rule.cooldown
+            });
+            if held_for < state.rule.duration || cooling_down {
+                continue;
+            }
+
+            state.firing = true;
+            state.last_fired = Some(now);
+            events.push(AlertEvent {
+                rule: state.rule.name.clone(),
+                kind: AlertEventKind::Firing,
+                time: now,
+                breaches,
+            });
+        }
+
+        for event in &events {
+            if self.history.len() == HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(event.clone());
+        }
+        events
+    }
+
+    fn notifies(&self, rule_name: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|state| state.rule.name == rule_name && state.rule.notify)
+    }
+}
+
+#[derive(Default)]
+pub struct AlertsState(Mutex<Alerts>);
+
+impl AlertsState {
+    pub fn set_rules(&self, rules: &[AlertRule]) {
+        self.0.lock().unwrap().set_rules(rules);
+    }
+
+    /// Checks the rules against a snapshot, letting the frontend know about any alerts that fired
+    /// or were resolved, and showing a notification for the ones that fired.
+    pub fn check(&self, app_handle: &AppHandle, data: &Data) {
+        let samples = metrics::samples(data, false);
+        let mut alerts = self.0.lock().unwrap();
+        for event in alerts.evaluate(&samples, unix_time_millis()) {
+            let _ = app_handle.emit_all("alert", &event);
+
+            if event.kind == AlertEventKind::Firing && alerts.notifies(&event.rule) {
+                let identifier = &app_handle.config().tauri.bundle.identifier;
+                let shown = Notification::new(identifier)
+                    .title(&event.rule)
+                    .body(event.summary())
+                    .show();
+                if let Err(err) = shown {
+                    log_at!(
+                        warn,
+                        "Couldn't show a notification for {:?}: {}",
+                        event.rule,
+                        err
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the latest alerts that fired or were resolved, oldest first.
+#[tauri::command]
+pub fn get_alert_history(alerts: State<AlertsState>) -> Vec<AlertEvent> {
+    alerts.0.lock().unwrap().history.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::metrics::TEMPERATURE;
+
+    fn temperature(sensor: &str, value: f64) -> Sample {
+        Sample {
+            family: &TEMPERATURE,
+            labels: vec![("sensor", sensor.to_string())],
+            value,
+        }
+    }
+
+    #[test]
+    fn test_alert_hysteresis_and_cooldown() {
+        let mut alerts = Alerts::default();
+        alerts.set_rules(&[AlertRule {
+            name: "Hot".to_string(),
+            when: vec![AlertCondition {
+                metric: "toerings_temperature_celsius".to_string(),
+                labels: BTreeMap::new(),
+                above: Some(85.0),
+                below: None,
+                clear: Some(80.0),
+            }],
+            duration: Duration::from_secs(30),
+            cooldown: Duration::from_secs(60),
+            notify: true,
+        }]);
+        let mut kinds = |sensors: &[Sample], now: u64| -> Vec<AlertEventKind> {
+            let events = alerts.evaluate(sensors, now * 1000);
+            events.iter().map(|event| event.kind).collect()
+        };
+        let cool = temperature("cpu", 40.0);
+
+        // Not for long enough yet.
+        assert_eq!(kinds(&[cool.clone(), temperature("gpu", 90.0)], 0), []);
+        assert_eq!(kinds(&[cool.clone(), temperature("gpu", 90.0)], 20), []);
+        assert_eq!(
+            kinds(&[cool.clone(), temperature("gpu", 90.0)], 30),
+            [AlertEventKind::Firing]
+        );
+        // Below the threshold, but not below where it clears.
+        assert_eq!(kinds(&[cool.clone(), temperature("gpu", 82.0)], 35), []);
+        assert_eq!(
+            kinds(&[cool.clone(), temperature("gpu", 79.0)], 40),
+            [AlertEventKind::Resolved]
+        );
+
+        // Held long enough again, but it only fired 40s ago.
+        assert_eq!(kinds(&[temperature("gpu", 90.0)], 45), []);
+        assert_eq!(kinds(&[temperature("gpu", 90.0)], 80), []);
+        assert_eq!(
+            kinds(&[temperature("gpu", 90.0)], 90),
+            [AlertEventKind::Firing]
+        );
+
+        assert_eq!(
+            alerts.history[0].breaches,
+            [Breach {
+                series: "toerings_temperature_celsius{sensor=\"gpu\"}".to_string(),
+                value: 90.0,
+            }]
+        );
+        assert_eq!(alerts.history.len(), 3);
+    }
+}
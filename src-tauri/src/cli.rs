@@ -0,0 +1,288 @@
+//! Headless mode, for getting snapshots into scripts without starting the GUI.
+//!
+//! ```sh
+//! toerings --json                                  # one snapshot
+//! toerings --json --stream --interval 2s           # newline-delimited JSON, until interrupted
+//! toerings --json --select network,memory          # only some of it
+//! ```
+
+use std::{
+    io::{self, Write},
+    ops::ControlFlow,
+    path::PathBuf,
+    sync::Mutex,
+    time::Duration,
+};
+
+use serde_json::{Map, Value};
+
+use crate::collection;
+use crate::config::{self, Config};
+use crate::data_harvester::{Data, DataCollector, Subsystem, SubsystemSchedule};
+
+const USAGE: &str = "\
+Usage: toerings --json [--stream] [--interval DURATION] [--select NAMES] [--root PATH]
+
+Prints snapshots of the system's state as JSON, without starting the GUI.
+
+Options:
+    --json                 Print JSON instead of starting the GUI
+    --stream               Keep printing a snapshot per line, instead of printing one and exiting
+    --interval DURATION    How often to take snapshots while streaming, e.g. 500ms or 2s [default: 1s]
+    --select NAMES         Only collect and print these, separated by commas. Each name is either a
+                           subsystem (cpu, processes, temperature, network, memory, disks, io,
+                           batteries, containers, host) or a field of the snapshot, like
+                           list_of_processes
+    --root PATH            On Linux, read /proc, /sys and /etc from under PATH instead of from /,
+                           e.g. the host's filesystem mounted into a container [default: /]
+    -h, --help             Print this message
+
+The collector settings in the config file apply, except for the refresh intervals.";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Args {
+    pub stream: bool,
+    pub interval: Duration,
+    /// The fields to print, and the subsystems that produce them. `None` to print everything.
+    pub selection: Option<Selection>,
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    pub subsystems: Vec<Subsystem>,
+    pub fields: Vec<&'static str>,
+}
+
+impl Selection {
+    fn add(&mut self, name: &str) -> Result<(), String> {
+        if let Ok(subsystem) = name.parse::<Subsystem>() {
+            self.add_subsystem(subsystem, subsystem.fields());
+            return Ok(());
+        }
+
+        let owner = Subsystem::ALL.into_iter().find_map(|subsystem| {
+            let field = subsystem.fields().iter().find(|field| **field == name)?;
+            Some((subsystem, field))
+        });
+        match owner {
+            Some((subsystem, field)) => {
+                self.add_subsystem(subsystem, std::slice::from_ref(field));
+                Ok(())
+            }
+            None => Err(format!("unknown subsystem or field {:?}", name)),
+        }
+    }
+
+    fn add_subsystem(&mut self, subsystem: Subsystem, fields: &[&'static str]) {
+        if !self.subsystems.contains(&subsystem) {
+            self.subsystems.push(subsystem);
+        }
+        for field in fields {
+            if !self.fields.contains(field) {
+                self.fields.push(field);
+            }
+        }
+    }
+}
+
+/// Whether the arguments ask for headless mode rather than the GUI.
+pub fn is_requested(args: &[String]) -> bool {
+    args.iter()
+        .any(|arg| matches!(arg.as_str(), "--json" | "-h" | "--help"))
+}
+
+/// Parses the arguments. `Ok(None)` means help was asked for.
+pub fn parse_args(args: &[String]) -> Result<Option<Args>, String> {
+    let mut json = false;
+    let mut parsed = Args {
+        stream: false,
+        interval: Duration::from_secs(1),
+        selection: None,
+        root: PathBuf::from("/"),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+
+        match flag {
+            "-h" | "--help" => return Ok(None),
+            "--json" => json = true,
+            "--stream" => parsed.stream = true,
+            "--interval" => {
+                let value = value()?;
+                parsed.interval = humantime::parse_duration(&value)
+                    .map_err(|err| format!("invalid interval {:?}: {}", value, err))?;
+                if parsed.interval.is_zero() {
+                    return Err("the interval can't be zero".to_string());
+                }
+            }
+            "--select" => {
+                let selection = parsed.selection.get_or_insert_with(Selection::default);
+                for name in value()?.split(',').map(str::trim) {
+                    if !name.is_empty() {
+                        selection.add(name)?;
+                    }
+                }
+            }
+            "--root" => parsed.root = PathBuf::from(value()?),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+
+    if !json {
+        return Err("--json is required for headless mode".to_string());
+    }
+    Ok(Some(parsed))
+}
+
+/// Runs headless mode, returning the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let config = config::default_path()
+        .map(|path| Config::load(&path))
+        .unwrap_or_else(|| Ok(Config::default()))
+        .unwrap_or_else(|err| {
+            eprintln!("warning: {}; using the default config", err);
+            Config::default()
+        });
+
+    let mut collector = DataCollector::new();
+    if let Err(err) = config.apply(&mut collector) {
+        eprintln!("warning: {}", err);
+    }
+    collector.set_root(args.root.clone());
+    let mut schedule = collector.schedule().clone();
+    for subsystem in Subsystem::ALL {
+        let enabled = match &args.selection {
+            Some(selection) => selection.subsystems.contains(&subsystem),
+            None => schedule.is_enabled(subsystem),
+        };
+        schedule.set(
+            subsystem,
+            SubsystemSchedule {
+                enabled,
+                interval: args.interval,
+            },
+        );
+    }
+    collector.set_schedule(schedule);
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut exit_code = 0;
+    collection::run(&Mutex::new(collector), |data| {
+        let printed = serde_json::to_writer(&mut stdout, &select(data, &args.selection))
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(stdout))
+            .and_then(|()| stdout.flush());
+
+        match printed {
+            // Most likely the reader went away, like `head` does.
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => ControlFlow::Break(()),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                exit_code = 1;
+                ControlFlow::Break(())
+            }
+            Ok(()) if args.stream => ControlFlow::Continue(()),
+            Ok(()) => ControlFlow::Break(()),
+        }
+    });
+
+    exit_code
+}
+
+fn select(data: &Data, selection: &Option<Selection>) -> Value {
+    let value = serde_json::to_value(data).unwrap_or_default();
+    match (value, selection) {
+        (Value::Object(fields), Some(selection)) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(field, _)| selection.fields.contains(&field.as_str()))
+                .collect::<Map<_, _>>(),
+        ),
+        (value, _) => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = parse_args(&args(&[
+            "--json",
+            "--stream",
+            "--interval=2s",
+            "--select",
+            "network, list_of_processes,processes",
+            "--root",
+            "/host",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert!(parsed.stream);
+        assert_eq!(parsed.interval, Duration::from_secs(2));
+        assert_eq!(parsed.root, PathBuf::from("/host"));
+        assert_eq!(
+            parsed.selection,
+            Some(Selection {
+                subsystems: vec![Subsystem::Network, Subsystem::Processes],
+                fields: vec!["network", "list_of_processes"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_usage_lists_subsystems() {
+        let (_, listed) = USAGE.split_once("subsystem (").unwrap();
+        let (listed, _) = listed.split_once(')').unwrap();
+        let names: Vec<&str> = listed.split(',').map(str::trim).collect();
+        assert_eq!(names, Subsystem::ALL.map(Subsystem::name));
+    }
+
+    #[test]
+    fn test_parse_bad_args() {
+        for bad in [
+            &["--stream"][..],
+            &["--json", "--interval"],
+            &["--json", "--interval", "soon"],
+            &["--json", "--select", "gpus"],
+            &["--json", "--verbose"],
+        ] {
+            assert!(
+                parse_args(&args(bad)).is_err(),
+                "{:?} should be rejected",
+                bad
+            );
+        }
+    }
+}
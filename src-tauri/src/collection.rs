@@ -0,0 +1,143 @@
+//! Runs the [`DataCollector`] on its own thread at a fixed rate, handing off each snapshot as soon
+//! as it's ready instead of waiting for the frontend to ask for one.
+
+use std::{
+    ops::ControlFlow,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::data_harvester::{Data, DataCollector};
+
+/// How long to wait after the priming pass before the first real collection. The rate-based values
+/// (CPU usage, network and IO throughput) need two samples some distance apart to mean anything.
+const PRIMING_DELAY: Duration = Duration::from_millis(250);
+
+/// Schedules ticks on a fixed grid anchored at the first tick, so the time spent collecting doesn't
+/// push every following tick back.
+#[derive(Debug)]
+pub struct Ticker {
+    interval: Duration,
+    next_tick: Instant,
+}
+
+impl Ticker {
+    pub fn new(interval: Duration, first_tick: Instant) -> Self {
+        Ticker {
+            // A zero interval would spin, and makes the catch-up math below divide by zero.
+            interval: interval.max(Duration::from_millis(1)),
+            next_tick: first_tick,
+        }
+    }
+
+    /// Changes the interval, starting with the wait for the upcoming tick.
+    pub fn set_interval(&mut self, interval: Duration) {
+        let interval = interval.max(Duration::from_millis(1));
+        self.next_tick = self.next_tick - self.interval + interval;
+        self.interval = interval;
+    }
+
+    /// Returns how long to wait from `now` until the upcoming tick, and moves the schedule past it.
+    ///
+    /// If we've fallen behind by one or more whole intervals (say the machine was suspended), the
+    /// missed ticks are skipped rather than fired in a burst.
+    pub fn next_delay(&mut self, now: Instant) -> Duration {
+        if self.next_tick < now {
+            let missed = (now - self.next_tick).as_nanos() / self.interval.as_nanos();
+            self.next_tick += self.interval * missed as u32;
+        }
+
+        let delay = self.next_tick.saturating_duration_since(now);
+        self.next_tick += self.interval;
+        delay
+    }
+}
+
+/// Spawns the collection thread, which ticks as often as the collector's schedule asks for.
+/// `on_data` is called with every new snapshot, from the collection thread.
+pub fn spawn<F>(collector: Arc<Mutex<DataCollector>>, mut on_data: F) -> JoinHandle<()>
+where
+    F: FnMut(&Data) + Send + 'static,
+{
+    thread::Builder::new()
+        .name("data-collection".to_string())
+        .spawn(move || {
+            run(&collector, |data| {
+                on_data(data);
+                ControlFlow::Continue(())
+            })
+        })
+        .expect("failed to spawn the data collection thread")
+}
+
+/// Runs the collection loop on the current thread, until `on_data` says to stop.
+pub fn run<F>(collector: &Mutex<DataCollector>, mut on_data: F)
+where
+    F: FnMut(&Data) -> ControlFlow<()>,
+{
+    let interval = {
+        let mut collector = collector.lock().unwrap();
+        collector.init();
+        futures::executor::block_on(collector.prime());
+        collector.tick_interval()
+    };
+
+    let mut ticker = Ticker::new(interval, Instant::now() + PRIMING_DELAY);
+    loop {
+        thread::sleep(ticker.next_delay(Instant::now()));
+
+        let data = {
+            let mut collector = collector.lock().unwrap();
+            futures::executor::block_on(collector.update_data());
+            // The schedule may have been changed since the last tick.
+            ticker.set_interval(collector.tick_interval());
+            collector.data.clone()
+        };
+        if on_data(&data).is_break() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticker_stays_on_grid() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(1);
+        let mut ticker = Ticker::new(interval, start);
+
+        assert_eq!(ticker.next_delay(start), Duration::ZERO);
+
+        // A slow collection shouldn't shift the following ticks.
+        assert_eq!(
+            ticker.next_delay(start + Duration::from_millis(300)),
+            Duration::from_millis(700)
+        );
+        assert_eq!(
+            ticker.next_delay(start + Duration::from_millis(1900)),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn test_ticker_skips_missed_ticks() {
+        let start = Instant::now();
+        let interval = Duration::from_secs(1);
+        let mut ticker = Ticker::new(interval, start);
+        ticker.next_delay(start);
+
+        // Woke up 3.5 intervals late: fire once right away, then get back on the grid.
+        assert_eq!(
+            ticker.next_delay(start + Duration::from_millis(3500)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            ticker.next_delay(start + Duration::from_millis(3600)),
+            Duration::from_millis(400)
+        );
+    }
+}
@@ -0,0 +1,822 @@
+//! The config file, `~/.config/toerings/config.toml`.
+//!
+//! It holds both the preferences the frontend applies (colors and font) and the settings for the
+//! [`DataCollector`]. A missing file, or a missing key within it, means the default.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use notify::{RecursiveMode, Watcher};
+
+use regex::RegexBuilder;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tauri::{AppHandle, Manager};
+
+use crate::alerts::AlertsState;
+use crate::data_harvester::{
+    CollectionSchedule, CollectorOptions, DataCollector, DataFilters, Filter, Subsystem,
+    SubsystemSchedule,
+};
+use crate::exporter::ExporterState;
+use crate::history::HistoryState;
+use crate::metrics::FAMILIES;
+use crate::utils::error::{self, ToeError};
+use crate::utils::logging::{self, log_at, LogLevel};
+
+/// Anything shorter would have the collection thread hogging a core.
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub appearance: AppearanceConfig,
+    pub collection: CollectionConfig,
+    pub filters: FiltersConfig,
+    pub exporter: ExporterConfig,
+    pub history: HistoryConfig,
+    pub logging: LoggingConfig,
+    pub alerts: Vec<AlertRule>,
+}
+
+/// Colors are CSS color strings, which are parsed by the frontend.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppearanceConfig {
+    pub foreground_color: String,
+    pub background_color: String,
+    pub title_color: String,
+    pub accent_color: String,
+    pub arc_track_color: String,
+    pub arc_cap_color: String,
+    pub font_family: String,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        AppearanceConfig {
+            foreground_color: "#ffffff".to_string(),
+            background_color: "rgba(0, 0, 0, 0.5)".to_string(),
+            title_color: "#00ff00".to_string(),
+            accent_color: "#ff00ff".to_string(),
+            arc_track_color: "rgba(255, 255, 255, 0.2)".to_string(),
+            arc_cap_color: "#ffffff".to_string(),
+            font_family: "Inter, Avenir, Helvetica, Arial, sans-serif".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectionConfig {
+    pub show_average_cpu: bool,
+    pub use_current_cpu_total: bool,
+    pub unnormalized_cpu: bool,
+    /// Whether to count the files each process has open. It takes a directory listing for every
+    /// process on each refresh.
+    pub count_open_files: bool,
+    /// How many of the processes using the most memory to read the proportional and unique set
+    /// sizes of, from `/proc/<pid>/smaps_rollup`. 0 turns it off.
+    pub proportional_memory_processes: usize,
+
+    /// Subsystems that aren't listed are refreshed at their default interval.
+    pub intervals: BTreeMap<Subsystem, RefreshInterval>,
+}
+
+/// How often a subsystem is refreshed. In the file this is a duration like `"3s"` or `"500ms"`,
+/// `true` for the default interval, or `false` to turn the subsystem off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshInterval {
+    Off,
+    Default,
+    Every(Duration),
+}
+
+impl Serialize for RefreshInterval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            RefreshInterval::Off => serializer.serialize_bool(false),
+            RefreshInterval::Default => serializer.serialize_bool(true),
+            RefreshInterval::Every(interval) => {
+                serializer.serialize_str(&humantime::format_duration(*interval).to_string())
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RefreshInterval {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IntervalVisitor;
+
+        impl<'de> de::Visitor<'de> for IntervalVisitor {
+            type Value = RefreshInterval;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a duration like \"3s\", or a boolean")
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                Ok(if value {
+                    RefreshInterval::Default
+                } else {
+                    RefreshInterval::Off
+                })
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                humantime::parse_duration(value)
+                    .map(RefreshInterval::Every)
+                    .map_err(|err| E::custom(format!("invalid duration {:?}: {}", value, err)))
+            }
+        }
+
+        deserializer.deserialize_any(IntervalVisitor)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FiltersConfig {
+    /// Matched against the device name of each disk, e.g. `/dev/sda1`.
+    pub disk: Option<FilterConfig>,
+    /// Matched against the mount point of each disk.
+    pub mount: Option<FilterConfig>,
+    /// Matched against the name of each temperature sensor.
+    pub temperature: Option<FilterConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// Whether matching entries are hidden, rather than the only ones shown.
+    pub is_list_ignored: bool,
+    pub list: Vec<String>,
+    /// Whether the entries in `list` are regular expressions, rather than plain substrings.
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Whether an entry has to match the whole text, rather than any part of it.
+    pub whole_word: bool,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            is_list_ignored: true,
+            list: Vec::new(),
+            regex: false,
+            case_sensitive: false,
+            whole_word: false,
+        }
+    }
+}
+
+impl FilterConfig {
+    fn compile(&self, name: &str) -> error::Result<Filter> {
+        let list = self
+            .list
+            .iter()
+            .map(|entry| {
+                let pattern = if self.regex {
+                    entry.clone()
+                } else {
+                    regex::escape(entry)
+                };
+                let pattern = if self.whole_word {
+                    format!("^(?:{})$", pattern)
+                } else {
+                    pattern
+                };
+
+                RegexBuilder::new(&pattern)
+                    .case_insensitive(!self.case_sensitive)
+                    .build()
+                    .map_err(|err| {
+                        ToeError::ConfigError(format!(
+                            "invalid pattern in `filters.{}`: {}",
+                            name, err
+                        ))
+                    })
+            })
+            .collect::<error::Result<_>>()?;
+
+        Ok(Filter {
+            is_list_ignored: self.is_list_ignored,
+            list,
+        })
+    }
+}
+
+/// The Prometheus exporter. It's only reachable from this machine unless `address` says otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExporterConfig {
+    pub enabled: bool,
+    pub address: SocketAddr,
+    /// Whether to export a series per process, which adds up to a lot of series over time.
+    pub include_processes: bool,
+}
+
+impl Default for ExporterConfig {
+    fn default() -> Self {
+        ExporterConfig {
+            enabled: false,
+            address: SocketAddr::from(([127, 0, 0, 1], 9184)),
+            include_processes: false,
+        }
+    }
+}
+
+/// How much of the graphs' history to keep.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Whether to save the history to a file, so it's still there after a restart.
+    pub persist: bool,
+    /// Older points are dropped. It's never kept for longer than a week.
+    #[serde(with = "humantime_serde")]
+    pub max_age: Duration,
+    /// The most disk space the saved history may take up.
+    pub max_size_mib: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            persist: true,
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+            max_size_mib: 64,
+        }
+    }
+}
+
+/// What gets written to the log file. See [`crate::utils::logging`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Overridden by the `TOERINGS_LOG` environment variable.
+    pub level: LogLevel,
+}
+
+/// Raises an alert when all its conditions have held for a while, e.g.
+///
+/// ```toml
+/// [[alerts]]
+/// name = "Running out of battery"
+/// for = "30s"
+///
+/// [[alerts.when]]
+/// metric = "toerings_battery_charge_ratio"
+/// below = 0.1
+/// clear = 0.15
+///
+/// [[alerts.when]]
+/// metric = "toerings_battery_discharging"
+/// above = 0.5
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    pub name: String,
+    pub when: Vec<AlertCondition>,
+    /// How long the conditions have to hold before the alert fires.
+    #[serde(rename = "for", default, with = "humantime_serde")]
+    pub duration: Duration,
+    /// How long after firing the alert stays quiet, even if it's resolved and the conditions hold
+    /// again.
+    #[serde(default = "default_cooldown", with = "humantime_serde")]
+    pub cooldown: Duration,
+    /// Whether to show a desktop notification when the alert fires.
+    #[serde(default = "default_true")]
+    pub notify: bool,
+}
+
+fn default_cooldown() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Holds when any series of `metric` with the given labels is above or below a threshold. Values
+/// are in the metric's units, so ratios rather than percentages; see [`crate::metrics`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertCondition {
+    pub metric: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    pub above: Option<f64>,
+    pub below: Option<f64>,
+    /// Once the alert fires, the condition holds until the value is back past this, rather than
+    /// the threshold, so a value hovering around the threshold doesn't keep resolving and firing
+    /// the alert. Defaults to the threshold.
+    pub clear: Option<f64>,
+}
+
+impl AlertCondition {
+    fn validate(&self) -> Result<(), String> {
+        if !FAMILIES
+            .iter()
+            .any(|family| family.sample_name() == self.metric)
+        {
+            return Err(format!("there's no metric called `{}`", self.metric));
+        }
+        match (self.above, self.below, self.clear) {
+            (Some(_), Some(_), _) | (None, None, _) => {
+                Err("exactly one of `above` and `below` is needed".to_string())
+            }
+            (Some(above), None, Some(clear)) if clear > above => {
+                Err("`clear` can't be above `above`".to_string())
+            }
+            (None, Some(below), Some(clear)) if clear < below => {
+                Err("`clear` can't be below `below`".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Where the config file lives. On macOS this is also under `~/.config` rather than
+/// `~/Library/Application Support`, so the file can be kept with the rest of one's dotfiles.
+pub fn default_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|home| home.join(".config"))
+    } else {
+        dirs::config_dir()
+    };
+    config_dir.map(|dir| dir.join("toerings").join("config.toml"))
+}
+
+/// Why the config file couldn't be loaded. Syntax errors and mistyped values come with the position
+/// in the file where they were found, 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigFileError {
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl ConfigFileError {
+    fn from_toml(err: &toml::de::Error, contents: &str) -> Self {
+        let (line, column) = match err.span() {
+            Some(span) => {
+                let before = &contents[..span.start];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                (
+                    Some(before.matches('\n').count() + 1),
+                    Some(before[line_start..].chars().count() + 1),
+                )
+            }
+            None => (None, None),
+        };
+
+        ConfigFileError {
+            message: err.message().to_string(),
+            line,
+            column,
+        }
+    }
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{} (line {}, column {})", self.message, line, column)
+            }
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+impl From<ToeError> for ConfigFileError {
+    fn from(err: ToeError) -> Self {
+        let message = match err {
+            ToeError::ConfigError(message) => message,
+            err => err.to_string(),
+        };
+        ConfigFileError {
+            message,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+impl From<ConfigFileError> for ToeError {
+    fn from(err: ConfigFileError) -> Self {
+        ToeError::ConfigError(err.to_string())
+    }
+}
+
+impl Config {
+    /// Reads and validates the config file. If there isn't one, the defaults are used.
+    pub fn load(path: &Path) -> Result<Config, ConfigFileError> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => {
+                return Err(ToeError::ConfigError(format!(
+                    "couldn't read {}: {}",
+                    path.display(),
+                    err
+                ))
+                .into())
+            }
+        };
+        Config::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, ConfigFileError> {
+        let config: Config =
+            toml::from_str(contents).map_err(|err| ConfigFileError::from_toml(&err, contents))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Writes the config file, creating its directory if need be.
+    pub fn save(&self, path: &Path) -> error::Result<()> {
+        let contents = toml::to_string_pretty(self)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write the whole thing elsewhere first, so that anything reading the file never sees it
+        // half-written.
+        let tmp_path = path.with_extension("toml.tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Checks the things that parsing alone doesn't.
+    pub fn validate(&self) -> error::Result<()> {
+        for (subsystem, interval) in &self.collection.intervals {
+            if let RefreshInterval::Every(interval) = interval {
+                if *interval < MIN_INTERVAL {
+                    return Err(ToeError::ConfigError(format!(
+                        "the `{}` interval must be at least {}",
+                        subsystem.name(),
+                        humantime::format_duration(MIN_INTERVAL)
+                    )));
+                }
+            }
+        }
+        self.filters()?;
+        for (i, rule) in self.alerts.iter().enumerate() {
+            let invalid = |reason: String| {
+                ToeError::ConfigError(format!("the alert `{}` is invalid: {}", rule.name, reason))
+            };
+            if self.alerts[..i].iter().any(|other| other.name == rule.name) {
+                return Err(invalid("another alert has the same name".to_string()));
+            }
+            if rule.when.is_empty() {
+                return Err(invalid("it needs at least one condition".to_string()));
+            }
+            for condition in &rule.when {
+                condition.validate().map_err(invalid)?;
+            }
+        }
+        if self.history.max_age < Duration::from_secs(60) {
+            return Err(ToeError::ConfigError(
+                "the history's `max_age` must be at least a minute".to_string(),
+            ));
+        }
+        if self.history.max_size_mib == 0 {
+            return Err(ToeError::ConfigError(
+                "the history's `max_size_mib` must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn collector_options(&self) -> CollectorOptions {
+        CollectorOptions {
+            show_average_cpu: self.collection.show_average_cpu,
+            use_current_cpu_total: self.collection.use_current_cpu_total,
+            unnormalized_cpu: self.collection.unnormalized_cpu,
+            count_open_files: self.collection.count_open_files,
+            proportional_memory_processes: self.collection.proportional_memory_processes,
+        }
+    }
+
+    pub fn schedule(&self) -> CollectionSchedule {
+        let mut schedule = CollectionSchedule::default();
+        for (&subsystem, interval) in &self.collection.intervals {
+            match interval {
+                RefreshInterval::Off => schedule.set(
+                    subsystem,
+                    SubsystemSchedule {
+                        enabled: false,
+                        ..schedule.get(subsystem)
+                    },
+                ),
+                RefreshInterval::Default => {}
+                RefreshInterval::Every(interval) => {
+                    schedule.set(subsystem, SubsystemSchedule::every(*interval))
+                }
+            }
+        }
+        schedule
+    }
+
+    pub fn filters(&self) -> error::Result<DataFilters> {
+        let compile = |filter: &Option<FilterConfig>, name| {
+            filter
+                .as_ref()
+                .map(|filter| filter.compile(name))
+                .transpose()
+        };
+
+        Ok(DataFilters {
+            disk_filter: compile(&self.filters.disk, "disk")?,
+            mount_filter: compile(&self.filters.mount, "mount")?,
+            temp_filter: compile(&self.filters.temperature, "temperature")?,
+        })
+    }
+
+    /// Hands the collector settings over to `collector`.
+    pub fn apply(&self, collector: &mut DataCollector) -> error::Result<()> {
+        let filters = self.filters()?;
+        collector.set_options(self.collector_options());
+        collector.set_schedule(self.schedule());
+        collector.set_filters(filters);
+        Ok(())
+    }
+
+    /// Applies the backend's settings to the running app.
+    pub fn apply_to_app(&self, app_handle: &AppHandle) -> error::Result<()> {
+        let collector = app_handle.state::<Arc<Mutex<DataCollector>>>();
+        self.apply(&mut collector.lock().unwrap())?;
+        app_handle
+            .state::<HistoryState>()
+            .configure(&self.history)?;
+        app_handle.state::<AlertsState>().set_rules(&self.alerts);
+        logging::set_level(self.logging.level);
+        app_handle
+            .state::<ExporterState>()
+            .configure(app_handle, &self.exporter)
+    }
+}
+
+/// The config file, and what was last loaded from it.
+pub struct ConfigState {
+    path: Option<PathBuf>,
+    config: Mutex<Result<Config, ConfigFileError>>,
+}
+
+impl ConfigState {
+    pub fn load() -> Self {
+        let path = default_path();
+        let config = match &path {
+            Some(path) => Config::load(path),
+            None => Ok(Config::default()),
+        };
+        ConfigState {
+            path,
+            config: Mutex::new(config),
+        }
+    }
+
+    /// The loaded config, or why it couldn't be loaded.
+    pub fn config(&self) -> Result<Config, ConfigFileError> {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Loads the config file again. Returns the result if it's any different from last time.
+    pub fn reload(&self) -> Option<Result<Config, ConfigFileError>> {
+        let loaded = Config::load(self.path.as_ref()?);
+        let mut config = self.config.lock().unwrap();
+        if *config == loaded {
+            return None;
+        }
+        *config = loaded.clone();
+        Some(loaded)
+    }
+}
+
+/// The payload of the `configChanged` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChanged {
+    Config(Box<Config>),
+    /// The collector keeps running with the last valid config.
+    Error(ConfigFileError),
+}
+
+/// Editors tend to save in several steps (truncate then write, or write a temporary file then
+/// rename it), so wait for things to settle before reloading.
+const SETTLE_DELAY: Duration = Duration::from_millis(100);
+
+/// Spawns a thread that reloads the config file whenever it changes, applies it to the collector
+/// and emits `configChanged`.
+pub fn watch(app_handle: AppHandle) -> JoinHandle<()> {
+    thread::Builder::new()
+        .name("config-watcher".to_string())
+        .spawn(move || {
+            if let Err(err) = watch_config(&app_handle) {
+                log_at!(error, "Stopped watching the config file: {}", err);
+            }
+        })
+        .expect("failed to spawn the config watcher thread")
+}
+
+fn watch_config(app_handle: &AppHandle) -> error::Result<()> {
+    let config_state = app_handle.state::<ConfigState>();
+    let path = config_state.path.as_ref().ok_or_else(|| {
+        ToeError::ConfigError("couldn't find a directory for the config file".to_string())
+    })?;
+    let (dir, file_name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(file_name)) => (dir, file_name),
+        _ => {
+            return Err(ToeError::ConfigError(format!(
+                "bad path {}",
+                path.display()
+            )))
+        }
+    };
+
+    // Watch the directory rather than the file, so that the file can be created after startup, and
+    // so we don't lose track of it when an editor replaces it with a new one.
+    fs::create_dir_all(dir)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+    let is_relevant = |event: notify::Result<notify::Event>| match event {
+        Ok(event) => {
+            !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path.file_name() == Some(file_name))
+        }
+        Err(_) => false,
+    };
+
+    while let Ok(event) = rx.recv() {
+        if !is_relevant(event) {
+            continue;
+        }
+        while rx.recv_timeout(SETTLE_DELAY).is_ok() {}
+
+        let payload = match config_state.reload() {
+            // Also the case after `set_config` writes the file.
+            None => continue,
+            Some(Ok(config)) => match config.apply_to_app(app_handle) {
+                Ok(()) => ConfigChanged::Config(Box::new(config)),
+                Err(err) => ConfigChanged::Error(err.into()),
+            },
+            Some(Err(err)) => ConfigChanged::Error(err),
+        };
+        let _ = app_handle.emit_all("configChanged", payload);
+    }
+
+    Ok(())
+}
+
+/// Returns the config, or why the config file couldn't be loaded. In that case the collector runs
+/// with the defaults, or with the last valid config if it was broken after startup.
+#[tauri::command]
+pub fn get_config(config_state: tauri::State<ConfigState>) -> Result<Config, ConfigFileError> {
+    config_state.config()
+}
+
+/// Validates `config`, writes it to the config file and applies it.
+#[tauri::command]
+pub fn set_config(
+    config: Config,
+    config_state: tauri::State<ConfigState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let set = || {
+        let path = config_state.path.as_ref().ok_or_else(|| {
+            ToeError::ConfigError("couldn't find a directory for the config file".to_string())
+        })?;
+        config.validate()?;
+        config.save(path)?;
+        config.apply_to_app(&app_handle)
+    };
+    set().map_err(|err| err.to_string())?;
+
+    *config_state.config.lock().unwrap() = Ok(config);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r##"
+            [appearance]
+            accent_color = "#00ffff"
+
+            [collection]
+            show_average_cpu = true
+
+            [collection.intervals]
+            processes = "3s"
+            disks = true
+            batteries = false
+
+            [filters.mount]
+            is_list_ignored = true
+            list = ["^/boot", "^/snap/"]
+            regex = true
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(config.appearance.accent_color, "#00ffff");
+        assert_eq!(config.appearance.title_color, "#00ff00");
+        assert!(config.collector_options().show_average_cpu);
+
+        let schedule = config.schedule();
+        assert_eq!(
+            schedule.get(Subsystem::Processes),
+            SubsystemSchedule::every(Duration::from_secs(3))
+        );
+        assert_eq!(
+            schedule.get(Subsystem::Disks),
+            CollectionSchedule::default().get(Subsystem::Disks)
+        );
+        assert!(!schedule.is_enabled(Subsystem::Batteries));
+
+        let filters = config.filters().unwrap();
+        assert!(!filters.keep_disk("/dev/sda1", "/boot/efi"));
+        assert!(filters.keep_disk("/dev/sda2", "/"));
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut config = Config::default();
+        config.collection.intervals.insert(
+            Subsystem::Cpu,
+            RefreshInterval::Every(Duration::from_millis(1500)),
+        );
+        config
+            .collection
+            .intervals
+            .insert(Subsystem::Host, RefreshInterval::Off);
+        config.filters.temperature = Some(FilterConfig {
+            list: vec!["nvme".to_string()],
+            ..FilterConfig::default()
+        });
+        config.alerts.push(AlertRule {
+            name: "Root is full".to_string(),
+            when: vec![AlertCondition {
+                metric: "toerings_disk_free_bytes".to_string(),
+                labels: BTreeMap::from([("mount_point".to_string(), "/".to_string())]),
+                above: None,
+                below: Some(5e9),
+                clear: None,
+            }],
+            duration: Duration::ZERO,
+            cooldown: Duration::from_secs(60 * 60),
+            notify: true,
+        });
+
+        let contents = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(Config::parse(&contents).unwrap(), config);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let invalid = [
+            "[collection.intervals]\ncpu = \"fast\"",
+            "[collection.intervals]\ngpu = \"1s\"",
+            "[collection.intervals]\ncpu = \"10ms\"",
+            "[filters.disk]\nlist = [\"(\"]\nregex = true",
+            "[apperance]\nfont_family = \"Inter\"",
+            "[logging]\nlevel = \"verbose\"",
+            "[[alerts]]\nname = \"Hot\"\n[[alerts.when]]\nmetric = \"toerings_temp\"\nabove = 85.0",
+            "[[alerts]]\nname = \"Hot\"\n[[alerts.when]]\nmetric = \"toerings_temperature_celsius\"\nabove = 85.0\nclear = 90.0",
+        ];
+        for contents in invalid {
+            assert!(
+                Config::parse(contents).is_err(),
+                "{:?} should be invalid",
+                contents
+            );
+        }
+    }
+
+    #[test]
+    fn test_config_error_position() {
+        let err = Config::parse(
+            "[collection]\nshow_average_cpu = true\n\n[collection.intervals]\n  cpu = 1\n",
+        )
+        .unwrap_err();
+        assert_eq!((err.line, err.column), (Some(5), Some(9)));
+    }
+}
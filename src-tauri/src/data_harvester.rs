@@ -1,18 +1,17 @@
 //! This is the main file to house data collection functions.
 
 use std::{
+    collections::BTreeMap,
     net::IpAddr,
-    time::{Duration, Instant},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-use futures::join;
+use futures::future::join_all;
 
-#[cfg(target_os = "linux")]
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 
-use serde::Serialize;
-#[cfg(feature = "battery")]
-use starship_battery::{Battery, Manager};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use sysinfo::{System, SystemExt};
 
@@ -21,16 +20,38 @@ pub mod nvidia;
 
 #[cfg(feature = "battery")]
 pub mod batteries;
+pub mod cgroups;
+pub mod containers;
 pub mod cpu;
 pub mod disks;
+pub mod filter;
+pub mod harvester;
+pub mod host;
 pub mod memory;
 pub mod network;
 pub mod processes;
+pub mod schedule;
 pub mod temperature;
 
-#[derive(Clone, Debug, Serialize)]
+pub use filter::{DataFilters, Filter};
+pub use harvester::{CollectorOptions, Harvester};
+pub use schedule::{CollectionSchedule, Subsystem, SubsystemSchedule};
+
+use harvester::{HarvestContext, SysRefresh};
+
+use crate::utils::error::ToeError;
+use crate::utils::logging::log_at;
+use crate::Pid;
+
+/// Refreshes that take longer than this get logged.
+const SLOW_REFRESH: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Data {
-    #[serde(with = "serde_millis")]
+    #[serde(
+        serialize_with = "serde_millis::serialize",
+        deserialize_with = "deserialize_now"
+    )]
     pub last_collection_time: Instant,
     pub cpu: Option<cpu::CpuHarvest>,
     pub load_avg: Option<cpu::LoadAvgHarvest>,
@@ -39,6 +60,7 @@ pub struct Data {
     pub temperature_sensors: Option<Vec<temperature::TempHarvest>>,
     pub network: Option<network::NetworkHarvest>,
     pub list_of_processes: Option<Vec<processes::ProcessHarvest>>,
+    pub containers: Option<Vec<containers::ContainerHarvest>>,
     pub disks: Option<Vec<disks::DiskHarvest>>,
     pub io: Option<disks::IoHarvest>,
     #[serde(with = "humantime_serde")]
@@ -55,6 +77,25 @@ pub struct Data {
     pub arc: Option<memory::MemHarvest>,
     #[cfg(feature = "gpu")]
     pub gpu: Option<Vec<(String, memory::MemHarvest)>>,
+    /// When each subsystem last refreshed successfully, in milliseconds since the Unix epoch.
+    /// Subsystems that are disabled or haven't succeeded yet are absent.
+    #[serde(default)]
+    pub refreshed_at: BTreeMap<Subsystem, u64>,
+    /// How each subsystem's refreshes have been going, so stale values can be told apart and
+    /// failures diagnosed. Subsystems that are disabled or haven't been refreshed yet are absent.
+    #[serde(default)]
+    pub collector_status: BTreeMap<Subsystem, CollectorStatus>,
+}
+
+/// When the subsystem last refreshed successfully is in [`Data::refreshed_at`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectorStatus {
+    /// Why the last refresh failed. Cleared by the next one that succeeds, at which point the
+    /// subsystem's values are current again.
+    pub last_error: Option<ToeError>,
+    /// How long the last refresh took, whether it succeeded or not.
+    #[serde(with = "humantime_serde")]
+    pub last_duration: Duration,
 }
 
 impl Default for Data {
@@ -67,6 +108,7 @@ impl Default for Data {
             swap: None,
             temperature_sensors: None,
             list_of_processes: None,
+            containers: None,
             disks: None,
             io: None,
             network: None,
@@ -82,6 +124,8 @@ impl Default for Data {
             arc: None,
             #[cfg(feature = "gpu")]
             gpu: None,
+            refreshed_at: BTreeMap::new(),
+            collector_status: BTreeMap::new(),
         }
     }
 }
@@ -91,11 +135,14 @@ impl Data {
         self.io = None;
         self.temperature_sensors = None;
         self.list_of_processes = None;
+        self.containers = None;
         self.disks = None;
         self.memory = None;
         self.swap = None;
         self.cpu = None;
         self.load_avg = None;
+        self.refreshed_at.clear();
+        self.collector_status.clear();
 
         if let Some(network) = &mut self.network {
             network.first_run_cleanup();
@@ -109,345 +156,297 @@ impl Data {
             self.gpu = None;
         }
     }
+
+    /// Drops everything a subsystem produced, e.g. once it's been disabled.
+    pub fn clear(&mut self, subsystem: Subsystem) {
+        match subsystem {
+            Subsystem::Cpu => {
+                self.cpu = None;
+                self.load_avg = None;
+            }
+            Subsystem::Processes => self.list_of_processes = None,
+            Subsystem::Temperature => self.temperature_sensors = None,
+            Subsystem::Network => self.network = None,
+            Subsystem::Memory => {
+                self.memory = None;
+                self.swap = None;
+                #[cfg(feature = "zfs")]
+                {
+                    self.arc = None;
+                }
+                #[cfg(feature = "gpu")]
+                {
+                    self.gpu = None;
+                }
+            }
+            Subsystem::Disks => self.disks = None,
+            Subsystem::Io => self.io = None,
+            Subsystem::Batteries => {
+                #[cfg(feature = "battery")]
+                {
+                    self.list_of_batteries = None;
+                }
+            }
+            Subsystem::Containers => self.containers = None,
+            Subsystem::Host => {
+                self.uptime = Duration::ZERO;
+                self.hostname = None;
+                self.kernel_name = None;
+                self.kernel_version = None;
+                self.os_version = None;
+                self.local_ip = None;
+            }
+        }
+        self.refreshed_at.remove(&subsystem);
+        self.collector_status.remove(&subsystem);
+    }
 }
 
 #[derive(Debug)]
 pub struct DataCollector {
     pub data: Data,
     sys: System,
-    previous_cpu_times: Vec<(cpu::PastCpuWork, cpu::PastCpuTotal)>,
-    previous_average_cpu_time: Option<(cpu::PastCpuWork, cpu::PastCpuTotal)>,
-    #[cfg(target_os = "linux")]
-    pid_mapping: FxHashMap<crate::Pid, processes::PrevProcDetails>,
-    #[cfg(target_os = "linux")]
-    prev_idle: f64,
-    #[cfg(target_os = "linux")]
-    prev_non_idle: f64,
-    mem_total_kb: u64,
-    use_current_cpu_total: bool,
-    unnormalized_cpu: bool,
-    last_collection_time: Instant,
-    total_rx: u64,
-    total_tx: u64,
-    show_average_cpu: bool,
-    #[cfg(feature = "battery")]
-    battery_manager: Option<Manager>,
-    #[cfg(feature = "battery")]
-    battery_list: Option<Vec<Battery>>,
-    #[cfg(target_family = "unix")]
-    user_table: self::processes::UserTable,
+    harvesters: Vec<Box<dyn Harvester>>,
+    options: CollectorOptions,
+    filters: DataFilters,
+    /// See [`HarvestContext::root`].
+    root: PathBuf,
+    schedule: CollectionSchedule,
+    /// See [`HarvestContext::thread_pids`].
+    thread_pids: FxHashSet<Pid>,
+    /// When each subsystem was last refreshed, successfully or not.
+    last_refresh: FxHashMap<Subsystem, Instant>,
+    /// Refresh every enabled subsystem on the next update, regardless of the schedule.
+    refresh_all: bool,
 }
 
 impl DataCollector {
     pub fn new() -> Self {
+        #[allow(unused_mut)]
+        let mut harvesters: Vec<Box<dyn Harvester>> = vec![
+            Box::<cpu::CpuHarvester>::default(),
+            Box::<processes::ProcessHarvester>::default(),
+            Box::<temperature::TemperatureHarvester>::default(),
+            Box::<network::NetworkHarvester>::default(),
+            Box::<memory::MemoryHarvester>::default(),
+            Box::<disks::DiskHarvester>::default(),
+            Box::<disks::IoHarvester>::default(),
+            Box::<host::HostHarvester>::default(),
+        ];
+        #[cfg(feature = "battery")]
+        harvesters.push(Box::<batteries::BatteryHarvester>::default());
+        #[cfg(target_os = "linux")]
+        harvesters.push(Box::<containers::ContainerHarvester>::default());
+
         DataCollector {
             data: Data::default(),
             sys: System::new_with_specifics(sysinfo::RefreshKind::new()),
-            previous_cpu_times: vec![],
-            previous_average_cpu_time: None,
-            #[cfg(target_os = "linux")]
-            pid_mapping: FxHashMap::default(),
-            #[cfg(target_os = "linux")]
-            prev_idle: 0_f64,
-            #[cfg(target_os = "linux")]
-            prev_non_idle: 0_f64,
-            mem_total_kb: 0,
-            use_current_cpu_total: false,
-            unnormalized_cpu: false,
-            last_collection_time: Instant::now(),
-            total_rx: 0,
-            total_tx: 0,
-            show_average_cpu: false,
-            #[cfg(feature = "battery")]
-            battery_manager: None,
-            #[cfg(feature = "battery")]
-            battery_list: None,
-            #[cfg(target_family = "unix")]
-            user_table: Default::default(),
+            harvesters,
+            options: CollectorOptions::default(),
+            filters: DataFilters::default(),
+            root: PathBuf::from("/"),
+            schedule: CollectionSchedule::default(),
+            thread_pids: FxHashSet::default(),
+            last_refresh: FxHashMap::default(),
+            refresh_all: false,
         }
     }
 
-    pub fn init(&mut self) {
-        #[cfg(target_os = "linux")]
-        {
-            futures::executor::block_on(self.initialize_memory_size());
-        }
-        #[cfg(not(target_os = "linux"))]
-        {
-            self.sys.refresh_memory();
-            self.mem_total_kb = self.sys.total_memory();
-
-            // TODO: Would be good to get this and network list running on a timer instead...?
-            // Refresh components list once...
-            self.sys.refresh_components_list();
-
-            // Refresh network list once...
-            if cfg!(target_os = "windows") {
-                self.sys.refresh_networks_list();
-            }
-
-            self.sys.refresh_cpu();
-
-            // Refresh disk list once...
-            if cfg!(target_os = "freebsd") {
-                self.sys.refresh_disks_list();
-            }
-        }
+    /// Adds another data source. Must be called before [`init`](Self::init).
+    pub fn register(&mut self, harvester: Box<dyn Harvester>) {
+        self.harvesters.push(harvester);
+    }
 
-        #[cfg(feature = "battery")]
-        {
-            if let Ok(battery_manager) = Manager::new() {
-                if let Ok(batteries) = battery_manager.batteries() {
-                    let battery_list: Vec<Battery> = batteries.filter_map(Result::ok).collect();
-                    if !battery_list.is_empty() {
-                        self.battery_list = Some(battery_list);
-                        self.battery_manager = Some(battery_manager);
-                    }
-                }
+    pub fn init(&mut self) {
+        for harvester in &mut self.harvesters {
+            // A harvester that failed to set up will fail its refreshes too, which is where
+            // failures get dealt with.
+            if let Err(err) = harvester.init(&mut self.sys) {
+                log_at!(
+                    warn,
+                    "Couldn't set up {}: {}",
+                    harvester.subsystem().name(),
+                    err
+                );
             }
         }
+    }
 
-        futures::executor::block_on(self.update_data());
-
-        std::thread::sleep(std::time::Duration::from_millis(250));
+    pub fn options(&self) -> &CollectorOptions {
+        &self.options
+    }
 
-        self.data.cleanup();
+    pub fn set_options(&mut self, options: CollectorOptions) {
+        self.options = options;
     }
 
-    #[cfg(target_os = "linux")]
-    async fn initialize_memory_size(&mut self) {
-        self.mem_total_kb = if let Ok(mem) = heim::memory::memory().await {
-            mem.total().get::<heim::units::information::kilobyte>()
-        } else {
-            1
-        };
+    pub fn filters(&self) -> &DataFilters {
+        &self.filters
     }
 
-    pub async fn update_data(&mut self) {
-        #[cfg(not(target_os = "linux"))]
-        {
-            self.sys.refresh_cpu();
-            self.sys.refresh_processes();
-            self.sys.refresh_components();
+    /// Takes effect from the next refresh of each filtered subsystem.
+    pub fn set_filters(&mut self, filters: DataFilters) {
+        self.filters = filters;
+    }
 
-            #[cfg(target_os = "windows")]
-            {
-                self.sys.refresh_networks();
-            }
+    /// Makes the Linux harvesters read from a filesystem mounted at `root` rather than at `/`,
+    /// e.g. a tree captured from another machine.
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = root;
+    }
 
-            #[cfg(target_os = "freebsd")]
-            {
-                self.sys.refresh_disks();
-                self.sys.refresh_memory();
-            }
+    /// Starts or stops listing the threads of the process `pid` in its [`ProcessHarvest`], from the
+    /// next refresh of the process list. It stops by itself once the process exits.
+    ///
+    /// [`ProcessHarvest`]: processes::ProcessHarvest
+    pub fn set_thread_collection(&mut self, pid: Pid, enabled: bool) {
+        if enabled {
+            self.thread_pids.insert(pid);
+        } else {
+            self.thread_pids.remove(&pid);
         }
+    }
 
-        let current_instant = std::time::Instant::now();
+    pub fn schedule(&self) -> &CollectionSchedule {
+        &self.schedule
+    }
 
-        // CPU
-        #[cfg(not(target_os = "freebsd"))]
-        {
-            if let Ok(cpu_data) = cpu::get_cpu_data_list(
-                self.show_average_cpu,
-                &mut self.previous_cpu_times,
-                &mut self.previous_average_cpu_time,
-            )
-            .await
-            {
-                self.data.cpu = Some(cpu_data);
-            }
-        }
-        #[cfg(target_os = "freebsd")]
-        {
-            if let Ok(cpu_data) = cpu::get_cpu_data_list(
-                &self.sys,
-                self.show_average_cpu,
-                &mut self.previous_cpu_times,
-                &mut self.previous_average_cpu_time,
-            )
-            .await
-            {
-                self.data.cpu = Some(cpu_data);
+    pub fn set_schedule(&mut self, schedule: CollectionSchedule) {
+        for subsystem in Subsystem::ALL {
+            if !schedule.is_enabled(subsystem) {
+                self.data.clear(subsystem);
+                self.last_refresh.remove(&subsystem);
             }
         }
+        self.schedule = schedule;
+    }
 
-        #[cfg(target_family = "unix")]
-        {
-            // Load Average
-            if let Ok(load_avg_data) = cpu::get_load_avg().await {
-                self.data.load_avg = Some(load_avg_data);
-            }
-        }
+    /// How often [`update_data`](Self::update_data) should be called.
+    pub fn tick_interval(&self) -> Duration {
+        self.schedule.tick_interval()
+    }
 
-        // Batteries
-        #[cfg(feature = "battery")]
-        {
-            if let Some(battery_manager) = &self.battery_manager {
-                if let Some(battery_list) = &mut self.battery_list {
-                    self.data.list_of_batteries =
-                        Some(batteries::refresh_batteries(battery_manager, battery_list));
-                }
-            }
+    fn is_due(&self, subsystem: Subsystem, now: Instant) -> bool {
+        let schedule = self.schedule.get(subsystem);
+        if !schedule.enabled {
+            return false;
+        } else if self.refresh_all {
+            return true;
         }
 
-        if let Ok(mut process_list) = {
-            #[cfg(target_os = "linux")]
-            {
-                // Must do this here since we otherwise have to make `get_process_data` async.
-                use self::processes::CpuUsageStrategy;
-
-                let normalize_cpu = if self.unnormalized_cpu {
-                    heim::cpu::logical_count()
-                        .await
-                        .map(|v| CpuUsageStrategy::NonNormalized(v as f64))
-                        .unwrap_or(CpuUsageStrategy::Normalized)
-                } else {
-                    CpuUsageStrategy::Normalized
-                };
-
-                processes::get_process_data(
-                    &mut self.prev_idle,
-                    &mut self.prev_non_idle,
-                    &mut self.pid_mapping,
-                    self.use_current_cpu_total,
-                    normalize_cpu,
-                    current_instant
-                        .duration_since(self.last_collection_time)
-                        .as_secs(),
-                    self.mem_total_kb,
-                    &mut self.user_table,
-                )
-            }
-            #[cfg(not(target_os = "linux"))]
-            {
-                #[cfg(target_family = "unix")]
-                {
-                    processes::get_process_data(
-                        &self.sys,
-                        self.use_current_cpu_total,
-                        self.unnormalized_cpu,
-                        self.mem_total_kb,
-                        &mut self.user_table,
-                    )
-                }
-                #[cfg(not(target_family = "unix"))]
-                {
-                    processes::get_process_data(
-                        &self.sys,
-                        self.use_current_cpu_total,
-                        self.unnormalized_cpu,
-                        self.mem_total_kb,
-                    )
-                }
-            }
-        } {
-            // NB: To avoid duplicate sorts on rerenders/events, we sort the processes by PID here.
-            // We also want to avoid re-sorting *again* later on if we're sorting by PID, since we already
-            // did it here!
-            process_list.sort_unstable_by_key(|p| p.pid);
-            self.data.list_of_processes = Some(process_list);
-        }
+        // Ticks never land exactly on schedule, so allow some slack. Otherwise a tick that fires a
+        // few ms early would push the refresh back by a whole tick.
+        let slack = self.tick_interval() / 2;
+        self.last_refresh.get(&subsystem).map_or(true, |last| {
+            now.duration_since(*last) + slack >= schedule.interval
+        })
+    }
 
-        #[cfg(not(target_os = "linux"))]
-        {
-            if let Ok(data) = temperature::get_temperature_data(&self.sys) {
-                self.data.temperature_sensors = data;
-            }
-        }
+    /// Takes a first sample of everything, so that the rate-based values (CPU usage, network and IO
+    /// throughput) have a baseline to be measured against. The sample itself is thrown away.
+    pub async fn prime(&mut self) {
+        self.update_data().await;
+        self.data.cleanup();
+        self.refresh_all = true;
+    }
 
-        #[cfg(target_os = "linux")]
-        {
-            if let Ok(data) = temperature::get_temperature_data() {
-                self.data.temperature_sensors = data;
-            }
+    /// Refreshes every subsystem that is due according to the schedule.
+    pub async fn update_data(&mut self) {
+        let now = Instant::now();
+        let due: Vec<bool> = self
+            .harvesters
+            .iter()
+            .map(|harvester| self.is_due(harvester.subsystem(), now))
+            .collect();
+
+        let sys_refreshes: FxHashSet<SysRefresh> = self
+            .harvesters
+            .iter()
+            .zip(&due)
+            .filter(|(_, due)| **due)
+            .flat_map(|(harvester, _)| harvester.sys_refreshes().iter().copied())
+            .collect();
+        for sys_refresh in sys_refreshes {
+            sys_refresh.apply(&mut self.sys);
         }
 
-        let network_data_fut = {
-            #[cfg(any(target_os = "windows", target_os = "freebsd"))]
-            {
-                network::get_network_data(
-                    &self.sys,
-                    self.last_collection_time,
-                    &mut self.total_rx,
-                    &mut self.total_tx,
-                    current_instant,
-                )
-            }
-            #[cfg(not(any(target_os = "windows", target_os = "freebsd")))]
-            {
-                network::get_network_data(
-                    self.last_collection_time,
-                    &mut self.total_rx,
-                    &mut self.total_tx,
-                    current_instant,
-                )
-            }
-        };
-        let mem_data_fut = {
-            #[cfg(not(target_os = "freebsd"))]
-            {
-                memory::get_mem_data()
-            }
-            #[cfg(target_os = "freebsd")]
-            {
-                memory::get_mem_data(&self.sys)
-            }
-        };
-        let disk_data_fut = disks::get_disk_usage();
-        let disk_io_usage_fut = disks::get_io_usage();
-
-        let (net_data, mem_res, disk_res, io_res) = join!(
-            network_data_fut,
-            mem_data_fut,
-            disk_data_fut,
-            disk_io_usage_fut,
+        let ctx = HarvestContext::new(
+            &self.sys,
+            now,
+            &self.options,
+            &self.filters,
+            &self.root,
+            &self.thread_pids,
+            &self.last_refresh,
         );
-
-        if let Ok(net_data) = net_data {
-            if let Some(net_data) = &net_data {
-                self.total_rx = net_data.total_rx;
-                self.total_tx = net_data.total_tx;
+        let results = join_all(
+            self.harvesters
+                .iter_mut()
+                .zip(&due)
+                .filter(|(_, due)| **due)
+                .map(|(harvester, _)| async {
+                    let start = Instant::now();
+                    let result = harvester.refresh(&ctx).await;
+                    (result, start.elapsed())
+                }),
+        )
+        .await;
+
+        let refreshed_at = unix_time_millis();
+        let due_harvesters = self
+            .harvesters
+            .iter_mut()
+            .zip(&due)
+            .filter(|(_, due)| **due);
+        for ((harvester, _), (result, duration)) in due_harvesters.zip(results) {
+            let subsystem = harvester.subsystem();
+            self.last_refresh.insert(subsystem, now);
+            let last_error = match result {
+                Ok(()) => {
+                    harvester.output(&mut self.data);
+                    self.data.refreshed_at.insert(subsystem, refreshed_at);
+                    None
+                }
+                Err(err) => Some(err),
+            };
+            let status = self.data.collector_status.entry(subsystem).or_default();
+            // Only changes are logged, or a subsystem that keeps failing would fill up the log.
+            match (&status.last_error, &last_error) {
+                (previous, Some(err)) if previous.as_ref() != Some(err) => {
+                    log_at!(warn, "Couldn't refresh {}: {}", subsystem.name(), err)
+                }
+                (Some(_), None) => log_at!(info, "Refreshed {} again", subsystem.name()),
+                _ => {}
             }
-            self.data.network = net_data;
-        }
-
-        if let Ok(memory) = mem_res.ram {
-            self.data.memory = memory;
-        }
-
-        if let Ok(swap) = mem_res.swap {
-            self.data.swap = swap;
-        }
-
-        #[cfg(feature = "zfs")]
-        if let Ok(arc) = mem_res.arc {
-            self.data.arc = arc;
-        }
-
-        #[cfg(feature = "gpu")]
-        if let Ok(gpu) = mem_res.gpus {
-            self.data.gpu = gpu;
-        }
-
-        if let Ok(disks) = disk_res {
-            self.data.disks = disks;
+            if duration > SLOW_REFRESH {
+                log_at!(warn, "Refreshing {} took {:?}", subsystem.name(), duration);
+            }
+            status.last_error = last_error;
+            status.last_duration = duration;
         }
 
-        if let Ok(io) = io_res {
-            self.data.io = io;
+        if let Some(processes) = &self.data.list_of_processes {
+            // So a new process that gets the same pid later doesn't have its threads listed.
+            self.thread_pids
+                .retain(|pid| processes.iter().any(|process| process.pid == *pid));
         }
+        self.data.last_collection_time = now;
+        self.refresh_all = false;
+    }
+}
 
-        self.data.uptime = Duration::from_secs(self.sys.uptime());
-        self.data.hostname = self.sys.host_name();
-        self.data.kernel_name = self.sys.name();
-        self.data.kernel_version = self.sys.kernel_version();
-        self.data.os_version = self.sys.long_os_version();
-        self.data.local_ip = local_ip_address::local_ip().ok();
+/// Recorded snapshots can be from before this machine booted, which an [`Instant`] can't represent,
+/// so they get the time they're read back at instead.
+fn deserialize_now<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Instant, D::Error> {
+    serde::de::IgnoredAny::deserialize(deserializer)?;
+    Ok(Instant::now())
+}
 
-        // Update time
-        self.data.last_collection_time = current_instant;
-        self.last_collection_time = current_instant;
-    }
+pub fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_millis() as u64)
+        .unwrap_or(0)
 }
 
 #[cfg(target_os = "freebsd")]
@@ -463,3 +462,102 @@ where
         .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "key not found"))
         .and_then(|val| serde_json::from_value(val).map_err(|err| err.into()))
 }
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Takes a snapshot of the tree in `tests/fixtures/<name>` and compares it with
+    /// `tests/fixtures/<name>.json`. Set `TOERINGS_UPDATE_FIXTURES` to rewrite that file instead.
+    fn check_fixture(name: &str) {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let mut collector = DataCollector::new();
+        collector.set_root(fixtures.join(name));
+        collector.set_options(CollectorOptions {
+            count_open_files: true,
+            proportional_memory_processes: 2,
+            ..CollectorOptions::default()
+        });
+        collector.init();
+        futures::executor::block_on(collector.update_data());
+
+        let mut data = serde_json::to_value(&collector.data).unwrap();
+        let snapshot = data.as_object_mut().unwrap();
+        // Only these don't come from the tree. The times are when the test ran, the local IP is
+        // this machine's, and disk space comes from the filesystem rather than from a file in it.
+        snapshot.remove("last_collection_time");
+        snapshot.remove("local_ip");
+        let refreshed: Vec<Value> = snapshot["refreshed_at"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|subsystem| Value::String(subsystem.clone()))
+            .collect();
+        snapshot["refreshed_at"] = Value::Array(refreshed);
+        for status in snapshot["collector_status"]
+            .as_object_mut()
+            .unwrap()
+            .values_mut()
+        {
+            let status = status.as_object_mut().unwrap();
+            status.remove("last_duration");
+        }
+        for process in snapshot["list_of_processes"].as_array_mut().unwrap() {
+            process.as_object_mut().unwrap().remove("elapsed_millis");
+        }
+        for disk in snapshot["disks"].as_array_mut().unwrap() {
+            let disk = disk.as_object_mut().unwrap();
+            for space in ["free_space", "used_space", "total_space"] {
+                disk.remove(space);
+            }
+        }
+        let actual = serde_json::to_string_pretty(&data).unwrap() + "\n";
+
+        let expected_path = fixtures.join(format!("{}.json", name));
+        if std::env::var_os("TOERINGS_UPDATE_FIXTURES").is_some() {
+            std::fs::write(&expected_path, actual).unwrap();
+        } else {
+            let expected = std::fs::read_to_string(&expected_path).unwrap();
+            assert_eq!(actual, expected, "the {} fixture's snapshot changed", name);
+        }
+    }
+
+    #[test]
+    fn test_laptop_fixture() {
+        check_fixture("laptop");
+    }
+
+    #[test]
+    fn test_server_fixture() {
+        check_fixture("server");
+    }
+
+    #[test]
+    fn test_container_fixture() {
+        check_fixture("container");
+    }
+
+    #[test]
+    fn test_failures_are_reported() {
+        let mut collector = DataCollector::new();
+        collector.set_root(PathBuf::from("/nonexistent"));
+        collector.init();
+        futures::executor::block_on(collector.update_data());
+
+        let status = &collector.data.collector_status[&Subsystem::Memory];
+        assert!(matches!(status.last_error, Some(ToeError::InvalidIo(_))));
+        assert!(collector.data.memory.is_none());
+        assert!(!collector.data.refreshed_at.contains_key(&Subsystem::Memory));
+
+        collector.set_root(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/laptop"));
+        collector.refresh_all = true;
+        futures::executor::block_on(collector.update_data());
+        let status = &collector.data.collector_status[&Subsystem::Memory];
+        assert_eq!(status.last_error, None);
+        assert!(collector.data.refreshed_at.contains_key(&Subsystem::Memory));
+    }
+}
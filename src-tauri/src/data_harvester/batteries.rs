@@ -2,9 +2,60 @@
 //!
 //! For Linux, macOS, Windows, FreeBSD, Dragonfly, and iOS, this is handled by the battery crate.
 
+use futures::future::LocalBoxFuture;
+use starship_battery::{Battery, Manager};
+
+use super::harvester::{HarvestContext, Harvester};
+use super::{Data, Subsystem};
+use crate::utils::error;
+
 cfg_if::cfg_if! {
     if #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux", target_os = "freebsd", target_os = "dragonfly", target_os = "ios"))] {
         pub mod battery;
         pub use self::battery::*;
     }
 }
+
+#[derive(Debug, Default)]
+pub struct BatteryHarvester {
+    battery_manager: Option<Manager>,
+    battery_list: Vec<Battery>,
+    batteries: Option<Vec<BatteryHarvest>>,
+}
+
+impl Harvester for BatteryHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Batteries
+    }
+
+    fn init(&mut self, _sys: &mut sysinfo::System) -> error::Result<()> {
+        if let Ok(battery_manager) = Manager::new() {
+            if let Ok(batteries) = battery_manager.batteries() {
+                let battery_list: Vec<Battery> = batteries.filter_map(Result::ok).collect();
+                if !battery_list.is_empty() {
+                    self.battery_list = battery_list;
+                    self.battery_manager = Some(battery_manager);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        _ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            if let Some(battery_manager) = &self.battery_manager {
+                self.batteries = Some(refresh_batteries(battery_manager, &mut self.battery_list));
+            }
+
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.list_of_batteries = self.batteries.take();
+    }
+}
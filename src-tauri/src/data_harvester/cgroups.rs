@@ -0,0 +1,295 @@
+//! Working out what a process belongs to from its cgroup: a container, a systemd unit, or a login
+//! session.
+//!
+//! Container runtimes and systemd both put each of their containers, services and sessions in a
+//! cgroup of its own, and name the cgroup after it, so the path alone is enough, e.g.
+//!
+//! ```text
+//! /system.slice/docker-4f3c2a9e81b7….scope
+//! /kubepods.slice/kubepods-burstable.slice/…/cri-containerd-9d1b7e….scope
+//! /user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-firefox-4410.scope
+//! /user.slice/user-1000.slice/session-3.scope
+//! ```
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContainerRuntime {
+    Docker,
+    Podman,
+    /// Including Kubernetes pods run through containerd, and nerdctl.
+    Containerd,
+    CriO,
+}
+
+impl ContainerRuntime {
+    pub fn name(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+            ContainerRuntime::Containerd => "containerd",
+            ContainerRuntime::CriO => "cri-o",
+        }
+    }
+}
+
+/// What started a process and keeps it running, for grouping processes by.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CgroupOwner {
+    /// `id` is the full container id.
+    Container {
+        runtime: ContainerRuntime,
+        id: String,
+    },
+    /// A systemd service or scope, like `nginx.service`. `user` is the uid whose user manager runs
+    /// it, if it isn't the system one.
+    Unit { name: String, user: Option<u32> },
+    /// A login session of the user `uid`, like a terminal or ssh login, numbered `id`.
+    Session { uid: u32, id: String },
+    /// A systemd slice with no unit under it, like `user-1000.slice`.
+    Slice { name: String },
+}
+
+impl fmt::Display for CgroupOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Abbreviated the way `docker ps` does.
+            CgroupOwner::Container { runtime, id } => {
+                write!(f, "{} {}", runtime.name(), &id[..id.len().min(12)])
+            }
+            CgroupOwner::Unit {
+                name,
+                user: Some(uid),
+            } => write!(f, "{} (user {})", name, uid),
+            CgroupOwner::Unit { name, user: None } => f.write_str(name),
+            CgroupOwner::Session { uid, id } => write!(f, "session {} (user {})", id, uid),
+            CgroupOwner::Slice { name } => f.write_str(name),
+        }
+    }
+}
+
+/// The cgroup path from the contents of `/proc/<pid>/cgroup`. With cgroup v2 there's a single line,
+/// `0::<path>`. With v1 there's a line per controller, and the one systemd names is used.
+pub fn parse_cgroup_file(contents: &str) -> Option<String> {
+    let path_of = |prefix: &str| {
+        contents
+            .lines()
+            .find_map(|line| line.split_once(prefix).map(|(_, path)| path))
+    };
+    // The hierarchy id comes first on each line, like `0::/init.scope` or `1:name=systemd:/`.
+    let path = contents
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .or_else(|| path_of(":name=systemd:"))?;
+    Some(path.trim().to_string())
+}
+
+/// A container id is 64 hex digits.
+fn container_id(id: &str) -> Option<&str> {
+    (id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())).then_some(id)
+}
+
+/// The container that the cgroup `name`, one part of a path, is for.
+fn container(name: &str, parent: Option<&str>) -> Option<CgroupOwner> {
+    const SCOPE_PREFIXES: [(&str, ContainerRuntime); 5] = [
+        ("docker-", ContainerRuntime::Docker),
+        ("libpod-", ContainerRuntime::Podman),
+        ("cri-containerd-", ContainerRuntime::Containerd),
+        ("nerdctl-", ContainerRuntime::Containerd),
+        ("crio-", ContainerRuntime::CriO),
+    ];
+
+    // With systemd managing the cgroups, like `docker-<id>.scope`.
+    if let Some(scope) = name.strip_suffix(".scope") {
+        return SCOPE_PREFIXES.iter().find_map(|(prefix, runtime)| {
+            let id = container_id(scope.strip_prefix(prefix)?)?;
+            Some(CgroupOwner::Container {
+                runtime: *runtime,
+                id: id.to_string(),
+            })
+        });
+    }
+
+    // Without systemd, the bare id goes under a cgroup named after the runtime, like
+    // `/docker/<id>`, or under the pod's, like `/kubepods/burstable/pod<uid>/<id>`.
+    let id = container_id(name)?;
+    let runtime = match parent? {
+        "docker" => ContainerRuntime::Docker,
+        "libpod_parent" => ContainerRuntime::Podman,
+        parent if parent.starts_with("pod") => ContainerRuntime::Containerd,
+        _ => return None,
+    };
+    Some(CgroupOwner::Container {
+        runtime,
+        id: id.to_string(),
+    })
+}
+
+/// Works out what owns the processes in the cgroup at `path`. `None` for the root cgroup, which is
+/// also what processes in a container see their own cgroup as.
+pub fn owner_of(path: &str) -> Option<CgroupOwner> {
+    let names: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+
+    // A container can be anywhere in the tree, and the innermost one is the most specific.
+    for (i, name) in names.iter().enumerate().rev() {
+        let parent = i.checked_sub(1).map(|parent| names[parent]);
+        if let Some(owner) = container(name, parent) {
+            return Some(owner);
+        }
+    }
+
+    // The position of `user@<uid>.service`, and the uid whose user manager runs everything below.
+    let user_manager = names.iter().enumerate().find_map(|(i, name)| {
+        let uid = name.strip_prefix("user@")?.strip_suffix(".service")?;
+        Some((i, uid.parse::<u32>().ok()?))
+    });
+    let unit = names
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(i, name)| {
+            (name.ends_with(".service") || name.ends_with(".scope"))
+                && user_manager.map_or(true, |(manager, _)| manager != *i)
+        })
+        .map(|(i, name)| (i, *name));
+
+    if let Some((i, name)) = unit {
+        if let Some((_, uid)) = user_manager.filter(|(manager, _)| *manager < i) {
+            return Some(CgroupOwner::Unit {
+                name: name.to_string(),
+                user: Some(uid),
+            });
+        }
+        let session = name
+            .strip_prefix("session-")
+            .and_then(|name| name.strip_suffix(".scope"));
+        let uid = i
+            .checked_sub(1)
+            .and_then(|parent| names[parent].strip_prefix("user-"))
+            .and_then(|slice| slice.strip_suffix(".slice"))
+            .and_then(|uid| uid.parse().ok());
+        if let (Some(id), Some(uid)) = (session, uid) {
+            return Some(CgroupOwner::Session {
+                uid,
+                id: id.to_string(),
+            });
+        }
+        return Some(CgroupOwner::Unit {
+            name: name.to_string(),
+            user: None,
+        });
+    }
+
+    // Everything under `user@<uid>.service` that isn't in a unit of its own is the manager itself.
+    if let Some((manager, _)) = user_manager {
+        return Some(CgroupOwner::Unit {
+            name: names[manager].to_string(),
+            user: None,
+        });
+    }
+    names.last().map(|name| CgroupOwner::Slice {
+        name: name.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_owner() {
+        let id = "4f3c2a9e81b7d6c5e0f1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f7";
+        let container = |runtime| {
+            Some(CgroupOwner::Container {
+                runtime,
+                id: id.to_string(),
+            })
+        };
+        let unit = |name: &str, user| {
+            Some(CgroupOwner::Unit {
+                name: name.to_string(),
+                user,
+            })
+        };
+
+        assert_eq!(
+            owner_of(&format!("/system.slice/docker-{}.scope", id)),
+            container(ContainerRuntime::Docker)
+        );
+        assert_eq!(
+            owner_of(&format!("/docker/{}", id)),
+            container(ContainerRuntime::Docker)
+        );
+        assert_eq!(
+            owner_of(&format!(
+                "/user.slice/user-1000.slice/user@1000.service/user.slice/libpod-{}.scope/container",
+                id
+            )),
+            container(ContainerRuntime::Podman)
+        );
+        assert_eq!(
+            owner_of(&format!(
+                "/kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod1a2b.slice/\
+                 cri-containerd-{}.scope",
+                id
+            )),
+            container(ContainerRuntime::Containerd)
+        );
+        assert_eq!(
+            owner_of(&format!("/kubepods/besteffort/pod1a2b-3c4d/{}", id)),
+            container(ContainerRuntime::Containerd)
+        );
+
+        assert_eq!(
+            owner_of("/system.slice/nginx.service"),
+            unit("nginx.service", None)
+        );
+        assert_eq!(owner_of("/init.scope"), unit("init.scope", None));
+        assert_eq!(
+            owner_of("/user.slice/user-1000.slice/user@1000.service/app.slice/app-gnome-firefox-4410.scope"),
+            unit("app-gnome-firefox-4410.scope", Some(1000))
+        );
+        assert_eq!(
+            owner_of("/user.slice/user-1000.slice/user@1000.service/init.scope"),
+            unit("init.scope", Some(1000))
+        );
+        assert_eq!(
+            owner_of("/user.slice/user-1000.slice/user@1000.service"),
+            unit("user@1000.service", None)
+        );
+        assert_eq!(
+            owner_of("/user.slice/user-1500.slice/session-7.scope"),
+            Some(CgroupOwner::Session {
+                uid: 1500,
+                id: "7".to_string()
+            })
+        );
+        assert_eq!(
+            owner_of("/user.slice/user-1000.slice"),
+            Some(CgroupOwner::Slice {
+                name: "user-1000.slice".to_string()
+            })
+        );
+        assert_eq!(owner_of("/"), None);
+    }
+
+    #[test]
+    fn test_parse_cgroup_file() {
+        assert_eq!(
+            parse_cgroup_file("0::/system.slice/nginx.service\n").as_deref(),
+            Some("/system.slice/nginx.service")
+        );
+        let v1 = "12:pids:/system.slice/nginx.service\n\
+                  2:cpu,cpuacct:/system.slice/nginx.service\n\
+                  1:name=systemd:/system.slice/nginx.service\n";
+        assert_eq!(
+            parse_cgroup_file(v1).as_deref(),
+            Some("/system.slice/nginx.service")
+        );
+        assert_eq!(parse_cgroup_file(""), None);
+    }
+}
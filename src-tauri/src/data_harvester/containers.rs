@@ -0,0 +1,55 @@
+//! Data collection for containers and systemd slices, from the cgroup v2 hierarchy under
+//! `/sys/fs/cgroup`. Only on Linux.
+//!
+//! Each container and slice has a cgroup of its own, which keeps count of what everything in it
+//! uses, so this adds up to the same as the processes in it would, including those that have
+//! already exited.
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        pub mod linux;
+        pub use self::linux::*;
+    }
+}
+
+use serde::{Deserialize, Serialize};
+
+use super::cgroups::CgroupOwner;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerHarvest {
+    /// The path of the cgroup, like `/system.slice/docker-4f3c….scope`.
+    pub cgroup: String,
+
+    /// A container, or a [`CgroupOwner::Slice`].
+    pub owner: CgroupOwner,
+
+    /// The container's name, if the runtime keeps its metadata where it can be read, and otherwise
+    /// what the owner shows as.
+    pub name: String,
+
+    /// CPU usage as a percentage, in the same terms as a process'.
+    pub cpu_usage_percent: f64,
+
+    /// How many scheduling periods the cgroup has run in, and in how many of those it was
+    /// throttled for going over its CPU limit. `None` without the cpu controller.
+    pub cpu_periods: Option<u64>,
+    pub throttled_periods: Option<u64>,
+
+    /// How long the cgroup has been throttled for in total, in milliseconds.
+    pub throttled_millis: Option<u64>,
+
+    /// Memory usage as bytes, including the page cache. `None` without the memory controller.
+    pub mem_usage_bytes: Option<u64>,
+
+    /// `None` without a limit.
+    pub mem_limit_bytes: Option<u64>,
+
+    pub read_bytes_per_sec: u64,
+    pub write_bytes_per_sec: u64,
+    pub total_read_bytes: u64,
+    pub total_write_bytes: u64,
+
+    /// How many processes and threads are in the cgroup. `None` without the pids controller.
+    pub pids: Option<u64>,
+}
@@ -0,0 +1,272 @@
+//! Reading the cgroup v2 hierarchy.
+
+use std::fs;
+use std::path::Path;
+
+use futures::future::LocalBoxFuture;
+use fxhash::FxHashMap;
+
+use super::ContainerHarvest;
+use crate::data_harvester::cgroups::{owner_of, CgroupOwner, ContainerRuntime};
+use crate::data_harvester::cpu::read_cpu_times;
+use crate::data_harvester::harvester::{under_root, HarvestContext, Harvester};
+use crate::data_harvester::{Data, Subsystem};
+use crate::utils::error::{self, ToeError};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+#[derive(Debug, Clone, Default)]
+struct PrevCgroupDetails {
+    usage_usec: u64,
+    total_read_bytes: u64,
+    total_write_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ContainerHarvester {
+    /// By cgroup path.
+    prev_details: FxHashMap<String, PrevCgroupDetails>,
+    /// The names from the runtimes' metadata, by container id. `None` if there wasn't one.
+    names: FxHashMap<String, Option<String>>,
+    containers: Option<Vec<ContainerHarvest>>,
+}
+
+impl Harvester for ContainerHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Containers
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            let cgroup_root = under_root(ctx.root, CGROUP_ROOT);
+            // Only there with cgroup v2, which the files below are laid out for.
+            if !cgroup_root.join("cgroup.controllers").exists() {
+                return Err(ToeError::GenericError(format!(
+                    "cgroup v2 isn't mounted at {}",
+                    CGROUP_ROOT
+                )));
+            }
+
+            let mut cgroups = Vec::new();
+            find_cgroups(&cgroup_root, "", &mut cgroups)?;
+            cgroups.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            let elapsed_secs = ctx
+                .last_refresh(Subsystem::Containers)
+                .map_or(0.0, |last| ctx.now.duration_since(last).as_secs_f64());
+            // The same as for processes: a share of the whole machine, unless it's asked to
+            // be a share of one CPU.
+            let cpu_count = if ctx.options.unnormalized_cpu {
+                1
+            } else {
+                read_cpu_times(ctx.root)
+                    .map(|times| times.per_cpu.len())
+                    .unwrap_or(1)
+                    .max(1)
+            };
+
+            let mut prev_details = FxHashMap::default();
+            let mut containers = Vec::with_capacity(cgroups.len());
+            for (path, owner) in cgroups {
+                let dir = cgroup_root.join(path.trim_start_matches('/'));
+                let Some((container, details)) = read_cgroup(
+                    &dir,
+                    path.clone(),
+                    owner,
+                    self.prev_details.get(&path),
+                    elapsed_secs,
+                    cpu_count,
+                ) else {
+                    // It was removed while this was running.
+                    continue;
+                };
+                prev_details.insert(path, details);
+                containers.push(container);
+            }
+            self.prev_details = prev_details;
+
+            self.name_containers(ctx.root, &mut containers);
+            self.containers = Some(containers);
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.containers = self.containers.take();
+    }
+}
+
+impl ContainerHarvester {
+    fn name_containers(&mut self, root: &Path, containers: &mut [ContainerHarvest]) {
+        let mut names = FxHashMap::default();
+        for container in containers {
+            let CgroupOwner::Container { runtime, id } = &container.owner else {
+                continue;
+            };
+            let name = self
+                .names
+                .remove(id)
+                .unwrap_or_else(|| read_container_name(root, *runtime, id));
+            if let Some(name) = &name {
+                container.name = name.clone();
+            }
+            names.insert(id.clone(), name);
+        }
+        // Only keeping the containers that are still running.
+        self.names = names;
+    }
+}
+
+/// Collects the cgroups under `dir`, which is at `path` in the hierarchy, that are for a
+/// container or a slice. Containers are as deep as this goes, as what's below them is up to
+/// whatever runs inside.
+fn find_cgroups(
+    dir: &Path,
+    path: &str,
+    cgroups: &mut Vec<(String, CgroupOwner)>,
+) -> error::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let Ok(entry) = entry else { continue };
+        if !entry
+            .file_type()
+            .map_or(false, |file_type| file_type.is_dir())
+        {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let child_path = format!("{}/{}", path, name);
+
+        match owner_of(&child_path) {
+            Some(owner @ CgroupOwner::Container { .. }) => {
+                cgroups.push((child_path, owner));
+                continue;
+            }
+            _ if name.ends_with(".slice") => {
+                cgroups.push((child_path.clone(), CgroupOwner::Slice { name }))
+            }
+            _ => {}
+        }
+        // It may have been removed since it was listed.
+        let _ = find_cgroups(&entry.path(), &child_path, cgroups);
+    }
+    Ok(())
+}
+
+/// The values of a file of `key value` lines, like `cpu.stat`.
+fn read_keyed(path: &Path) -> Option<FxHashMap<String, u64>> {
+    let contents = fs::read_to_string(path).ok()?;
+    let values = contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key.to_string(), value.trim().parse().ok()?))
+        })
+        .collect();
+    Some(values)
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The bytes read and written, added up over every device, from `io.stat`. Its lines are like
+/// `8:0 rbytes=1459200 wbytes=314773504 rios=192 wios=353 dbytes=0 dios=0`.
+fn read_io_stat(path: &Path) -> (u64, u64) {
+    let Ok(contents) = fs::read_to_string(path) else {
+        return (0, 0);
+    };
+    let mut totals = (0, 0);
+    for (key, value) in contents
+        .split_whitespace()
+        .filter_map(|field| field.split_once('='))
+    {
+        let value: u64 = value.parse().unwrap_or(0);
+        match key {
+            "rbytes" => totals.0 += value,
+            "wbytes" => totals.1 += value,
+            _ => {}
+        }
+    }
+    totals
+}
+
+fn read_cgroup(
+    dir: &Path,
+    path: String,
+    owner: CgroupOwner,
+    prev: Option<&PrevCgroupDetails>,
+    elapsed_secs: f64,
+    cpu_count: usize,
+) -> Option<(ContainerHarvest, PrevCgroupDetails)> {
+    // Always there, whichever controllers are enabled.
+    let cpu_stat = read_keyed(&dir.join("cpu.stat"))?;
+    let usage_usec = cpu_stat.get("usage_usec").copied().unwrap_or(0);
+    let (total_read_bytes, total_write_bytes) = read_io_stat(&dir.join("io.stat"));
+
+    // Rates need something to compare against.
+    let per_sec = |current: u64, prev: Option<u64>| match prev {
+        Some(prev) if elapsed_secs > 0.0 => {
+            (current.saturating_sub(prev) as f64 / elapsed_secs) as u64
+        }
+        _ => 0,
+    };
+    let cpu_usage_percent = match prev {
+        Some(prev) if elapsed_secs > 0.0 => {
+            let used_secs = usage_usec.saturating_sub(prev.usage_usec) as f64 / 1e6;
+            used_secs / elapsed_secs / cpu_count as f64 * 100.0
+        }
+        _ => 0.0,
+    };
+
+    let container = ContainerHarvest {
+        name: owner.to_string(),
+        owner,
+        cpu_usage_percent,
+        cpu_periods: cpu_stat.get("nr_periods").copied(),
+        throttled_periods: cpu_stat.get("nr_throttled").copied(),
+        throttled_millis: cpu_stat.get("throttled_usec").map(|usec| usec / 1000),
+        mem_usage_bytes: read_number(&dir.join("memory.current")),
+        // `max` when there's no limit.
+        mem_limit_bytes: read_number(&dir.join("memory.max")),
+        read_bytes_per_sec: per_sec(total_read_bytes, prev.map(|prev| prev.total_read_bytes)),
+        write_bytes_per_sec: per_sec(total_write_bytes, prev.map(|prev| prev.total_write_bytes)),
+        total_read_bytes,
+        total_write_bytes,
+        pids: read_number(&dir.join("pids.current")),
+        cgroup: path,
+    };
+    let details = PrevCgroupDetails {
+        usage_usec,
+        total_read_bytes,
+        total_write_bytes,
+    };
+    Some((container, details))
+}
+
+/// The name the runtime gave the container `id`, from where it keeps its metadata. Only
+/// Docker and Podman running as root keep it somewhere that can be read without asking them.
+fn read_container_name(root: &Path, runtime: ContainerRuntime, id: &str) -> Option<String> {
+    match runtime {
+        ContainerRuntime::Docker => {
+            let config_path = format!("/var/lib/docker/containers/{}/config.v2.json", id);
+            let config = fs::read_to_string(under_root(root, config_path)).ok()?;
+            let config: serde_json::Value = serde_json::from_str(&config).ok()?;
+            let name = config.get("Name")?.as_str()?;
+            Some(name.trim_start_matches('/').to_string())
+        }
+        ContainerRuntime::Podman => {
+            let containers_path = "/var/lib/containers/storage/overlay-containers/containers.json";
+            let containers = fs::read_to_string(under_root(root, containers_path)).ok()?;
+            let containers: Vec<serde_json::Value> = serde_json::from_str(&containers).ok()?;
+            let container = containers
+                .iter()
+                .find(|container| container.get("id").and_then(|id| id.as_str()) == Some(id))?;
+            let name = container.get("names")?.get(0)?.as_str()?;
+            Some(name.to_string())
+        }
+        ContainerRuntime::Containerd | ContainerRuntime::CriO => None,
+    }
+}
@@ -1,13 +1,23 @@
 //! Data collection for CPU usage and load average.
 //!
-//! For CPU usage, Linux, macOS, and Windows are handled by Heim, FreeBSD by sysinfo.
+//! For CPU usage, Linux reads `/proc/stat`, macOS and Windows are handled by Heim, FreeBSD by
+//! sysinfo.
 //!
-//! For load average, macOS and Linux are supported through Heim, FreeBSD by sysinfo.
+//! For load average, Linux reads `/proc/loadavg`, macOS is supported through Heim, FreeBSD by
+//! sysinfo.
 
-use serde::Serialize;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+
+use super::harvester::{HarvestContext, Harvester, SysRefresh};
+use super::{Data, Subsystem};
+use crate::utils::error;
 
 cfg_if::cfg_if! {
-    if #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))] {
+    if #[cfg(target_os = "linux")] {
+        pub mod linux;
+        pub use self::linux::*;
+    } else if #[cfg(any(target_os = "macos", target_os = "windows"))] {
         pub mod heim;
         pub use self::heim::*;
     } else if #[cfg(target_os = "freebsd")] {
@@ -18,13 +28,13 @@ cfg_if::cfg_if! {
 
 pub type LoadAvgHarvest = [f32; 3];
 
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CpuDataType {
     Avg,
     Cpu(usize),
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuData {
     pub data_type: CpuDataType,
     pub cpu_usage: f64,
@@ -36,3 +46,94 @@ pub type PastCpuWork = f64;
 pub type PastCpuTotal = f64;
 
 pub type Point = (f64, f64);
+
+#[derive(Debug, Default)]
+pub struct CpuHarvester {
+    previous_cpu_times: Vec<(PastCpuWork, PastCpuTotal)>,
+    previous_average_cpu_time: Option<(PastCpuWork, PastCpuTotal)>,
+    cpu: Option<CpuHarvest>,
+    load_avg: Option<LoadAvgHarvest>,
+}
+
+impl Harvester for CpuHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Cpu
+    }
+
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        if cfg!(target_os = "freebsd") {
+            &[SysRefresh::Cpu]
+        } else {
+            &[]
+        }
+    }
+
+    fn init(&mut self, _sys: &mut ::sysinfo::System) -> error::Result<()> {
+        #[cfg(target_os = "freebsd")]
+        {
+            use ::sysinfo::SystemExt;
+            _sys.refresh_cpu();
+        }
+
+        Ok(())
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            let cpu = {
+                #[cfg(target_os = "linux")]
+                {
+                    get_cpu_data_list(
+                        ctx.root,
+                        ctx.options.show_average_cpu,
+                        &mut self.previous_cpu_times,
+                        &mut self.previous_average_cpu_time,
+                    )
+                    .await?
+                }
+                #[cfg(any(target_os = "macos", target_os = "windows"))]
+                {
+                    get_cpu_data_list(
+                        ctx.options.show_average_cpu,
+                        &mut self.previous_cpu_times,
+                        &mut self.previous_average_cpu_time,
+                    )
+                    .await?
+                }
+                #[cfg(target_os = "freebsd")]
+                {
+                    get_cpu_data_list(
+                        ctx.sys,
+                        ctx.options.show_average_cpu,
+                        &mut self.previous_cpu_times,
+                        &mut self.previous_average_cpu_time,
+                    )
+                    .await?
+                }
+            };
+            self.cpu = Some(cpu);
+
+            // Load Average
+            #[cfg(target_os = "linux")]
+            if let Ok(load_avg) = get_load_avg(ctx.root).await {
+                self.load_avg = Some(load_avg);
+            }
+            #[cfg(all(not(target_os = "linux"), target_family = "unix"))]
+            if let Ok(load_avg) = get_load_avg().await {
+                self.load_avg = Some(load_avg);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.cpu = self.cpu.take();
+        if let Some(load_avg) = self.load_avg.take() {
+            data.load_avg = Some(load_avg);
+        }
+    }
+}
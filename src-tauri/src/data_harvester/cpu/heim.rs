@@ -1,15 +1,8 @@
 //! CPU stats through heim.
-//! Supports macOS, Linux, and Windows.
+//! Supports macOS and Windows.
 
-cfg_if::cfg_if! {
-    if #[cfg(target_os = "linux")] {
-        pub mod linux;
-        pub use linux::*;
-    } else if #[cfg(any(target_os = "macos", target_os = "windows"))] {
-        pub mod windows_macos;
-        pub use windows_macos::*;
-    }
-}
+pub mod windows_macos;
+pub use windows_macos::*;
 
 cfg_if::cfg_if! {
     if #[cfg(target_family = "unix")] {
@@ -30,7 +23,7 @@ pub async fn get_cpu_data_list(
     show_average_cpu: bool,
     previous_cpu_times: &mut Vec<(PastCpuWork, PastCpuTotal)>,
     previous_average_cpu_time: &mut Option<(PastCpuWork, PastCpuTotal)>,
-) -> crate::error::Result<CpuHarvest> {
+) -> crate::utils::error::Result<CpuHarvest> {
     fn calculate_cpu_usage_percentage(
         (previous_working_time, previous_total_time): Point,
         (current_working_time, current_total_time): Point,
@@ -2,7 +2,7 @@
 
 use crate::data_harvester::cpu::LoadAvgHarvest;
 
-pub async fn get_load_avg() -> crate::error::Result<LoadAvgHarvest> {
+pub async fn get_load_avg() -> crate::utils::error::Result<LoadAvgHarvest> {
     let (one, five, fifteen) = heim::cpu::os::unix::loadavg().await?;
 
     Ok([
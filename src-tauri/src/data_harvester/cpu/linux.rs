@@ -0,0 +1,148 @@
+//! CPU usage and load average on Linux, read from `/proc`.
+
+use std::{path::Path, time::Duration};
+
+use crate::data_harvester::cpu::{
+    CpuData, CpuDataType, CpuHarvest, LoadAvgHarvest, PastCpuTotal, PastCpuWork, Point,
+};
+use crate::data_harvester::harvester::under_root;
+use crate::utils::error::{self, ToeError};
+
+/// How long each CPU has spent working and how long in total, from `/proc/stat`. In clock ticks.
+#[derive(Debug, Clone)]
+pub struct CpuTimes {
+    /// Summed over all CPUs.
+    pub total: Point,
+    pub per_cpu: Vec<Point>,
+}
+
+pub fn read_cpu_times(root: &Path) -> error::Result<CpuTimes> {
+    let stat = std::fs::read_to_string(under_root(root, "/proc/stat"))?;
+
+    let mut total = None;
+    let mut per_cpu = Vec::new();
+    for line in stat.lines() {
+        let Some((label, values)) = line.split_once(' ') else {
+            continue;
+        };
+        if label == "cpu" {
+            total = Some(convert_cpu_times(values));
+        } else if label
+            .strip_prefix("cpu")
+            .map_or(false, |index| index.parse::<usize>().is_ok())
+        {
+            per_cpu.push(convert_cpu_times(values));
+        }
+    }
+
+    match total {
+        Some(total) => Ok(CpuTimes { total, per_cpu }),
+        None => Err(ToeError::GenericError(
+            "/proc/stat has no CPU times".to_string(),
+        )),
+    }
+}
+
+fn convert_cpu_times(values: &str) -> Point {
+    let mut values = values
+        .split_whitespace()
+        .map(|value| value.parse::<f64>().unwrap_or(0.0));
+    let mut next = || values.next().unwrap_or(0.0);
+    let (user, nice, system, idle, io_wait, irq, soft_irq, steal) = (
+        next(),
+        next(),
+        next(),
+        next(),
+        next(),
+        next(),
+        next(),
+        next(),
+    );
+
+    // Guest time is already counted in user and nice.
+    let working_time = user + nice + system + irq + soft_irq + steal;
+    (working_time, working_time + idle + io_wait)
+}
+
+fn calculate_cpu_usage_percentage(
+    (previous_working_time, previous_total_time): Point,
+    (current_working_time, current_total_time): Point,
+) -> f64 {
+    ((if current_working_time > previous_working_time {
+        current_working_time - previous_working_time
+    } else {
+        0.0
+    }) * 100.0)
+        / (if current_total_time > previous_total_time {
+            current_total_time - previous_total_time
+        } else {
+            1.0
+        })
+}
+
+pub async fn get_cpu_data_list(
+    root: &Path,
+    show_average_cpu: bool,
+    previous_cpu_times: &mut Vec<(PastCpuWork, PastCpuTotal)>,
+    previous_average_cpu_time: &mut Option<(PastCpuWork, PastCpuTotal)>,
+) -> error::Result<CpuHarvest> {
+    let mut current = read_cpu_times(root)?;
+
+    if previous_cpu_times.is_empty() || (show_average_cpu && previous_average_cpu_time.is_none()) {
+        // Must initialize ourselves.  Use a very quick timeout to calculate an initial.
+        futures_timer::Delay::new(Duration::from_millis(100)).await;
+
+        let initial = std::mem::replace(&mut current, read_cpu_times(root)?);
+        *previous_cpu_times = initial.per_cpu;
+        *previous_average_cpu_time = Some(initial.total);
+    }
+
+    let mut cpu_harvest = Vec::with_capacity(current.per_cpu.len() + 1);
+
+    // Get average CPU if needed... and slap it at the top
+    if show_average_cpu {
+        let previous = previous_average_cpu_time.unwrap_or(current.total);
+        cpu_harvest.push(CpuData {
+            data_type: CpuDataType::Avg,
+            cpu_usage: calculate_cpu_usage_percentage(previous, current.total),
+        });
+    }
+
+    cpu_harvest.extend(
+        current
+            .per_cpu
+            .iter()
+            .enumerate()
+            .map(|(itx, present_times)| {
+                // A CPU that just came online has nothing to compare with yet.
+                let previous = previous_cpu_times
+                    .get(itx)
+                    .copied()
+                    .unwrap_or(*present_times);
+                CpuData {
+                    data_type: CpuDataType::Cpu(itx),
+                    cpu_usage: calculate_cpu_usage_percentage(previous, *present_times),
+                }
+            }),
+    );
+
+    *previous_cpu_times = current.per_cpu;
+    *previous_average_cpu_time = Some(current.total);
+
+    Ok(cpu_harvest)
+}
+
+pub async fn get_load_avg(root: &Path) -> error::Result<LoadAvgHarvest> {
+    let loadavg = std::fs::read_to_string(under_root(root, "/proc/loadavg"))?;
+
+    let mut load_avg = [0.0; 3];
+    let mut values = loadavg.split_whitespace();
+    for value in &mut load_avg {
+        *value = values
+            .next()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| ToeError::ConversionError(format!("invalid loadavg {:?}", loadavg)))?;
+    }
+
+    Ok(load_avg)
+}
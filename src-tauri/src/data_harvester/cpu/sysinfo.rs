@@ -13,7 +13,7 @@ pub async fn get_cpu_data_list(
     show_average_cpu: bool,
     _previous_cpu_times: &mut [(PastCpuWork, PastCpuTotal)],
     _previous_average_cpu_time: &mut Option<(PastCpuWork, PastCpuTotal)>,
-) -> crate::error::Result<CpuHarvest> {
+) -> crate::utils::error::Result<CpuHarvest> {
     let mut cpu_deque: VecDeque<_> = sys
         .cpus()
         .iter()
@@ -36,7 +36,7 @@ pub async fn get_cpu_data_list(
     Ok(Vec::from(cpu_deque))
 }
 
-pub async fn get_load_avg() -> crate::error::Result<LoadAvgHarvest> {
+pub async fn get_load_avg() -> crate::utils::error::Result<LoadAvgHarvest> {
     let sys = System::new();
     let LoadAvg { one, five, fifteen } = sys.load_average();
 
@@ -1,12 +1,20 @@
 //! Data collection for disks (IO, usage, space, etc.).
 //!
-//! For Linux, macOS, and Windows, this is handled by heim. For FreeBSD there is a custom
-//! implementation.
+//! On Linux, this is read from `/proc`. For macOS and Windows, this is handled by heim. For FreeBSD
+//! there is a custom implementation.
 
-use serde::Serialize;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+
+use super::harvester::{HarvestContext, Harvester, SysRefresh};
+use super::{Data, Subsystem};
+use crate::utils::error;
 
 cfg_if::cfg_if! {
-    if #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))] {
+    if #[cfg(target_os = "linux")] {
+        pub mod linux;
+        pub use self::linux::*;
+    } else if #[cfg(any(target_os = "macos", target_os = "windows"))] {
         pub mod heim;
         pub use self::heim::*;
     } else if #[cfg(target_os = "freebsd")] {
@@ -15,7 +23,7 @@ cfg_if::cfg_if! {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DiskHarvest {
     pub name: String,
     pub mount_point: String,
@@ -24,10 +32,98 @@ pub struct DiskHarvest {
     pub total_space: Option<u64>,
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IoData {
     pub read_bytes: u64,
     pub write_bytes: u64,
 }
 
 pub type IoHarvest = std::collections::HashMap<String, Option<IoData>>;
+
+#[derive(Debug, Default)]
+pub struct DiskHarvester {
+    disks: Option<Vec<DiskHarvest>>,
+}
+
+impl Harvester for DiskHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Disks
+    }
+
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        if cfg!(target_os = "freebsd") {
+            &[SysRefresh::Disks]
+        } else {
+            &[]
+        }
+    }
+
+    fn init(&mut self, _sys: &mut sysinfo::System) -> error::Result<()> {
+        #[cfg(target_os = "freebsd")]
+        {
+            use sysinfo::SystemExt;
+
+            // Refresh disk list once...
+            _sys.refresh_disks_list();
+        }
+
+        Ok(())
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            #[cfg(target_os = "linux")]
+            let disks = get_disk_usage(ctx.root).await?;
+            #[cfg(not(target_os = "linux"))]
+            let disks = get_disk_usage().await?;
+
+            self.disks = disks.map(|disks| {
+                disks
+                    .into_iter()
+                    .filter(|disk| ctx.filters.keep_disk(&disk.name, &disk.mount_point))
+                    .collect()
+            });
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.disks = self.disks.take();
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IoHarvester {
+    io: Option<IoHarvest>,
+}
+
+impl Harvester for IoHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Io
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            #[cfg(target_os = "linux")]
+            let io = get_io_usage(ctx.root).await?;
+            #[cfg(not(target_os = "linux"))]
+            let io = {
+                let _ = ctx;
+                get_io_usage().await?
+            };
+
+            self.io = io;
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.io = self.io.take();
+    }
+}
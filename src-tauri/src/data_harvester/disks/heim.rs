@@ -1,17 +1,10 @@
 //! Disk stats through heim.
-//! Supports macOS, Linux, and Windows.
+//! Supports macOS and Windows.
 
 use crate::data_harvester::disks::{DiskHarvest, IoData, IoHarvest};
 
-cfg_if::cfg_if! {
-    if #[cfg(target_os = "linux")] {
-        pub mod linux;
-        pub use linux::*;
-    } else if #[cfg(any(target_os = "macos", target_os = "windows"))] {
-        pub mod windows_macos;
-        pub use windows_macos::*;
-    }
-}
+pub mod windows_macos;
+pub use windows_macos::*;
 
 pub async fn get_io_usage() -> crate::utils::error::Result<Option<IoHarvest>> {
     use futures::StreamExt;
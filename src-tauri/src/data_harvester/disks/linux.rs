@@ -0,0 +1,195 @@
+//! Disk usage and IO on Linux, read from `/proc`.
+
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use fxhash::FxHashSet;
+
+use crate::data_harvester::disks::{DiskHarvest, IoData, IoHarvest};
+use crate::data_harvester::harvester::under_root;
+use crate::utils::error;
+
+/// `/proc/diskstats` counts in 512-byte sectors, whatever the device's actual sector size.
+const SECTOR_SIZE: u64 = 512;
+
+pub async fn get_io_usage(root: &Path) -> error::Result<Option<IoHarvest>> {
+    let diskstats = std::fs::read_to_string(under_root(root, "/proc/diskstats"))?;
+    let mut io_hash: IoHarvest = HashMap::new();
+
+    for line in diskstats.lines() {
+        // major, minor, name, then reads completed, reads merged, sectors read, time spent reading,
+        // writes completed, writes merged, sectors written and so on.
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let (Some(name), Some(sectors_read), Some(sectors_written)) =
+            (fields.get(2), fields.get(5), fields.get(9))
+        {
+            io_hash.insert(
+                name.to_string(),
+                Some(IoData {
+                    read_bytes: sectors_read.parse::<u64>().unwrap_or(0) * SECTOR_SIZE,
+                    write_bytes: sectors_written.parse::<u64>().unwrap_or(0) * SECTOR_SIZE,
+                }),
+            );
+        }
+    }
+
+    Ok(Some(io_hash))
+}
+
+pub async fn get_disk_usage(root: &Path) -> error::Result<Option<Vec<DiskHarvest>>> {
+    let physical = physical_filesystems(root)?;
+    let mounts = std::fs::read_to_string(under_root(root, "/proc/mounts"))?;
+    let mut vec_disks: Vec<DiskHarvest> = Vec::new();
+
+    for line in mounts.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(device), Some(mount_point), Some(fs_type)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        if !physical.contains(fs_type) {
+            continue;
+        }
+
+        let device = unescape(device);
+        let mount_point = unescape(mount_point);
+        let name = get_device_name(root, &device);
+
+        // The usage line can fail in some cases (for example, if you use Void Linux + LUKS,
+        // see https://github.com/ClementTsang/bottom/issues/419 for details).  As such, check
+        // it like this instead.
+        if let Some((free, used, total)) = usage(&under_root(root, &mount_point)) {
+            vec_disks.push(DiskHarvest {
+                free_space: Some(free),
+                used_space: Some(used),
+                total_space: Some(total),
+                mount_point,
+                name,
+            });
+        } else {
+            vec_disks.push(DiskHarvest {
+                free_space: None,
+                used_space: None,
+                total_space: None,
+                mount_point,
+                name,
+            });
+        }
+    }
+
+    Ok(Some(vec_disks))
+}
+
+/// The filesystem types that live on a device, which are the ones listed in `/proc/filesystems`
+/// without `nodev`. ZFS is listed with it, but its datasets are still disks.
+fn physical_filesystems(root: &Path) -> error::Result<FxHashSet<String>> {
+    let filesystems = std::fs::read_to_string(under_root(root, "/proc/filesystems"))?;
+    let mut physical: FxHashSet<String> = filesystems
+        .lines()
+        .filter(|line| !line.starts_with("nodev"))
+        .map(|line| line.trim().to_string())
+        .filter(|fs_type| !fs_type.is_empty())
+        .collect();
+    physical.insert("zfs".to_string());
+
+    Ok(physical)
+}
+
+/// Undoes the octal escapes `/proc/mounts` uses for spaces, tabs, newlines and backslashes.
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match code {
+            Some(code) => {
+                unescaped.push(code as char);
+                rest = &rest[i + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+fn get_device_name(root: &Path, device: &str) -> String {
+    // See if this disk is actually mounted elsewhere on Linux...
+    // This is a workaround to properly map I/O in some cases (i.e. disk encryption), see
+    // https://github.com/ClementTsang/bottom/issues/419
+    let device_path = under_root(root, device);
+    match std::fs::read_link(&device_path) {
+        Ok(path) if path.is_absolute() => path.to_string_lossy().into_owned(),
+        Ok(path) => {
+            let mut combined_path = device_path;
+            combined_path.pop(); // Pop the current file...
+            combined_path.push(path);
+
+            // Resolve the local path into an absolute one...
+            let resolved = std::fs::canonicalize(combined_path)
+                .ok()
+                .and_then(|canon_path| {
+                    let root = std::fs::canonicalize(root).ok()?;
+                    let path = canon_path.strip_prefix(root).ok()?;
+                    Some(PathBuf::from("/").join(path))
+                });
+            match resolved {
+                Some(path) => path.to_string_lossy().into_owned(),
+                None => device.to_string(),
+            }
+        }
+        Err(_) => device.to_string(),
+    }
+}
+
+/// The free, used and total space of the filesystem `path` is on, in bytes.
+fn usage(path: &Path) -> Option<(u64, u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: `path` is NUL-terminated, and statvfs only writes to `stat`, which is only read if
+    // it succeeded.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+
+    // The field types differ between 32 and 64-bit platforms.
+    #[allow(clippy::unnecessary_cast)]
+    let (fragment_size, blocks, free_blocks, available_blocks) = (
+        stat.f_frsize as u64,
+        stat.f_blocks as u64,
+        stat.f_bfree as u64,
+        stat.f_bavail as u64,
+    );
+
+    Some((
+        available_blocks * fragment_size,
+        (blocks - free_blocks) * fragment_size,
+        blocks * fragment_size,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape_mount_fields() {
+        assert_eq!(unescape("/mnt/My\\040Passport"), "/mnt/My Passport");
+        assert_eq!(unescape("/a\\134b\\011c"), "/a\\b\tc");
+        assert_eq!(unescape("/trailing\\"), "/trailing\\");
+    }
+}
@@ -0,0 +1,64 @@
+//! Allow and deny lists for hiding disks and temperature sensors.
+
+use regex::Regex;
+
+/// A list of patterns that entries are matched against.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// Whether matching entries are hidden (a deny list), rather than the only ones shown (an allow
+    /// list).
+    pub is_list_ignored: bool,
+    pub list: Vec<Regex>,
+}
+
+impl Filter {
+    fn matches(&self, text: &str) -> bool {
+        self.list.iter().any(|regex| regex.is_match(text))
+    }
+}
+
+/// The filters applied to the collected data. A missing filter lets everything through.
+#[derive(Debug, Clone, Default)]
+pub struct DataFilters {
+    pub disk_filter: Option<Filter>,
+    pub mount_filter: Option<Filter>,
+    pub temp_filter: Option<Filter>,
+}
+
+impl DataFilters {
+    /// Whether to keep a disk, going by its device name and mount point.
+    ///
+    /// If the two filters disagree, "allow" takes precedence over "deny": a disk matching either
+    /// allow list is always kept. Otherwise it's kept unless it matches either deny list, or there's
+    /// an allow list it doesn't match.
+    pub fn keep_disk(&self, name: &str, mount_point: &str) -> bool {
+        let checks = [(&self.disk_filter, name), (&self.mount_filter, mount_point)];
+
+        let mut has_allow_list = false;
+        for (filter, text) in checks {
+            match filter {
+                Some(filter) if !filter.is_list_ignored => {
+                    if filter.matches(text) {
+                        return true;
+                    }
+                    has_allow_list = true;
+                }
+                _ => {}
+            }
+        }
+
+        let denied = checks.iter().any(|(filter, text)| match filter {
+            Some(filter) if filter.is_list_ignored => filter.matches(text),
+            _ => false,
+        });
+
+        !denied && !has_allow_list
+    }
+
+    pub fn keep_temperature(&self, name: &str) -> bool {
+        match &self.temp_filter {
+            Some(filter) => filter.matches(name) != filter.is_list_ignored,
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! The interface shared by every data source, so that [`DataCollector`](super::DataCollector) can
+//! drive them without knowing what each one needs on each platform.
+
+use std::{fmt, path::Path, time::Instant};
+
+use futures::future::LocalBoxFuture;
+use fxhash::{FxHashMap, FxHashSet};
+use sysinfo::{System, SystemExt};
+
+use super::{filter::DataFilters, Data, Subsystem};
+use crate::utils::error;
+use crate::Pid;
+
+/// Flags that change how some harvesters compute their values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CollectorOptions {
+    /// Whether to add the average usage over all cores in front of the per-core CPU usage.
+    pub show_average_cpu: bool,
+
+    /// Whether process CPU usage is relative to the current total CPU usage, rather than to the
+    /// total CPU capacity.
+    pub use_current_cpu_total: bool,
+
+    /// Whether process CPU usage is left as-is, rather than divided over the number of cores.
+    pub unnormalized_cpu: bool,
+
+    /// Whether to count each process' open files, which means listing a directory per process.
+    pub count_open_files: bool,
+
+    /// How many of the processes with the most resident memory to read the PSS and USS of, which
+    /// means reading through all of each one's memory mappings. 0 for none.
+    pub proportional_memory_processes: usize,
+}
+
+/// The parts of the shared [`System`] that a harvester reads from. Each is refreshed once per
+/// update, however many harvesters ask for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SysRefresh {
+    Cpu,
+    Processes,
+    Components,
+    Networks,
+    Disks,
+    Memory,
+}
+
+impl SysRefresh {
+    pub fn apply(self, sys: &mut System) {
+        match self {
+            SysRefresh::Cpu => sys.refresh_cpu(),
+            SysRefresh::Processes => sys.refresh_processes(),
+            SysRefresh::Components => sys.refresh_components(),
+            SysRefresh::Networks => sys.refresh_networks(),
+            SysRefresh::Disks => sys.refresh_disks(),
+            SysRefresh::Memory => sys.refresh_memory(),
+        }
+    }
+}
+
+/// What a harvester gets to work with during a refresh.
+pub struct HarvestContext<'a> {
+    /// Already refreshed for everything the due harvesters asked for. Unused on Linux, where
+    /// everything is read from under [`root`](Self::root) instead.
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
+    pub sys: &'a System,
+
+    /// When this update started.
+    pub now: Instant,
+
+    pub options: &'a CollectorOptions,
+
+    pub filters: &'a DataFilters,
+
+    /// Where the filesystem that the Linux harvesters read `/proc`, `/sys` and `/etc` from is
+    /// mounted. Normally `/`, but it can be a captured tree instead.
+    pub root: &'a Path,
+
+    /// The processes to list the threads of, besides counting them.
+    pub thread_pids: &'a FxHashSet<Pid>,
+
+    last_refresh: &'a FxHashMap<Subsystem, Instant>,
+}
+
+impl<'a> HarvestContext<'a> {
+    pub fn new(
+        sys: &'a System,
+        now: Instant,
+        options: &'a CollectorOptions,
+        filters: &'a DataFilters,
+        root: &'a Path,
+        thread_pids: &'a FxHashSet<Pid>,
+        last_refresh: &'a FxHashMap<Subsystem, Instant>,
+    ) -> Self {
+        HarvestContext {
+            sys,
+            now,
+            options,
+            filters,
+            root,
+            thread_pids,
+            last_refresh,
+        }
+    }
+
+    /// When `subsystem` was last refreshed, successfully or not. `None` if this is the first time.
+    pub fn last_refresh(&self, subsystem: Subsystem) -> Option<Instant> {
+        self.last_refresh.get(&subsystem).copied()
+    }
+}
+
+/// `path`, an absolute path on the collected machine, as seen from under `root`.
+pub fn under_root(root: &Path, path: impl AsRef<Path>) -> std::path::PathBuf {
+    let path = path.as_ref();
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// A source of data for one part of the snapshot.
+pub trait Harvester: fmt::Debug + Send {
+    /// The part of the snapshot this harvester produces. It's scheduled according to that
+    /// subsystem's interval.
+    fn subsystem(&self) -> Subsystem;
+
+    /// The parts of the shared [`System`] to refresh before [`refresh`](Self::refresh) is called.
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        &[]
+    }
+
+    /// One-time setup, done before the first refresh.
+    fn init(&mut self, _sys: &mut System) -> error::Result<()> {
+        Ok(())
+    }
+
+    /// Takes a new sample.
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>>;
+
+    /// Moves the sample from the last refresh into `data`. Only called if that refresh succeeded,
+    /// so a failure leaves the previous values in place.
+    fn output(&mut self, data: &mut Data);
+}
@@ -0,0 +1,111 @@
+//! Data collection for general information about the machine: uptime, hostname, kernel and OS
+//! versions, and the local IP.
+//!
+//! On Linux, this is read from `/proc` and `/etc`. Elsewhere, it's handled by sysinfo.
+
+#[cfg(target_os = "linux")]
+use std::path::Path;
+use std::{net::IpAddr, time::Duration};
+
+use futures::future::LocalBoxFuture;
+#[cfg(not(target_os = "linux"))]
+use sysinfo::SystemExt;
+
+#[cfg(target_os = "linux")]
+use super::harvester::under_root;
+use super::harvester::{HarvestContext, Harvester};
+use super::{Data, Subsystem};
+use crate::utils::error;
+
+#[derive(Debug, Clone, Default)]
+pub struct HostHarvest {
+    pub uptime: Duration,
+    pub hostname: Option<String>,
+    pub kernel_name: Option<String>,
+    pub kernel_version: Option<String>,
+    pub os_version: Option<String>,
+    pub local_ip: Option<IpAddr>,
+}
+
+#[derive(Debug, Default)]
+pub struct HostHarvester {
+    host: Option<HostHarvest>,
+}
+
+impl Harvester for HostHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Host
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            #[cfg(target_os = "linux")]
+            let host = get_host_data(ctx.root);
+            #[cfg(not(target_os = "linux"))]
+            let host = HostHarvest {
+                uptime: Duration::from_secs(ctx.sys.uptime()),
+                hostname: ctx.sys.host_name(),
+                kernel_name: ctx.sys.name(),
+                kernel_version: ctx.sys.kernel_version(),
+                os_version: ctx.sys.long_os_version(),
+                local_ip: local_ip_address::local_ip().ok(),
+            };
+            self.host = Some(host);
+
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        if let Some(host) = self.host.take() {
+            data.uptime = host.uptime;
+            data.hostname = host.hostname;
+            data.kernel_name = host.kernel_name;
+            data.kernel_version = host.kernel_version;
+            data.os_version = host.os_version;
+            data.local_ip = host.local_ip;
+        }
+    }
+}
+
+/// Gets the same values sysinfo would, but from under `root`.
+#[cfg(target_os = "linux")]
+fn get_host_data(root: &Path) -> HostHarvest {
+    let read = |path: &str| {
+        std::fs::read_to_string(under_root(root, path))
+            .ok()
+            .map(|contents| contents.trim().to_string())
+    };
+    // `/etc/lsb-release` is the fallback for distributions whose `/etc/os-release` lacks a field.
+    let os_release = |key: &str, lsb_key: &str| {
+        [("/etc/os-release", key), ("/etc/lsb-release", lsb_key)]
+            .into_iter()
+            .find_map(|(path, key)| {
+                read(path)?.lines().find_map(|line| {
+                    let value = line.strip_prefix(key)?.strip_prefix('=')?;
+                    Some(value.replace('"', ""))
+                })
+            })
+    };
+    let name = os_release("NAME", "DISTRIB_ID");
+    let version = os_release("VERSION_ID", "DISTRIB_RELEASE");
+
+    HostHarvest {
+        uptime: read("/proc/uptime")
+            .and_then(|uptime| uptime.split('.').next()?.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default(),
+        hostname: read("/proc/sys/kernel/hostname"),
+        kernel_version: read("/proc/sys/kernel/osrelease"),
+        os_version: Some(format!(
+            "Linux {} {}",
+            version.unwrap_or_default(),
+            name.as_deref().unwrap_or_default()
+        )),
+        kernel_name: name,
+        local_ip: local_ip_address::local_ip().ok(),
+    }
+}
@@ -1,6 +1,13 @@
 //! Data collection for memory.
 //!
-//! For Linux, macOS, and Windows, this is handled by Heim. On FreeBSD it is handled by sysinfo.
+//! On Linux, this is read from `/proc/meminfo`. For macOS and Windows, this is handled by Heim. On
+//! FreeBSD it is handled by sysinfo.
+
+use futures::future::LocalBoxFuture;
+
+use super::harvester::{HarvestContext, Harvester, SysRefresh};
+use super::{Data, Subsystem};
+use crate::utils::error;
 
 cfg_if::cfg_if! {
     if #[cfg(any(target_os = "freebsd", target_os = "linux", target_os = "macos", target_os = "windows"))] {
@@ -8,3 +15,77 @@ cfg_if::cfg_if! {
         pub use self::general::*;
     }
 }
+
+#[derive(Debug, Default)]
+pub struct MemoryHarvester {
+    collected: Option<MemCollect>,
+}
+
+impl Harvester for MemoryHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Memory
+    }
+
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        if cfg!(target_os = "freebsd") {
+            &[SysRefresh::Memory]
+        } else {
+            &[]
+        }
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            let collected = {
+                #[cfg(target_os = "linux")]
+                {
+                    get_mem_data(ctx.root).await
+                }
+                #[cfg(any(target_os = "macos", target_os = "windows"))]
+                {
+                    let _ = ctx;
+                    get_mem_data().await
+                }
+                #[cfg(target_os = "freebsd")]
+                {
+                    get_mem_data(ctx.sys).await
+                }
+            };
+
+            // RAM is what the memory widget is built around, so only count that as a failure.
+            // Swap, ARC and GPU memory are each kept as they were if they fail.
+            let result = match &collected.ram {
+                Ok(_) => Ok(()),
+                Err(err) => Err(err.clone()),
+            };
+            self.collected = Some(collected);
+
+            result
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        if let Some(collected) = self.collected.take() {
+            if let Ok(memory) = collected.ram {
+                data.memory = memory;
+            }
+
+            if let Ok(swap) = collected.swap {
+                data.swap = swap;
+            }
+
+            #[cfg(feature = "zfs")]
+            if let Ok(arc) = collected.arc {
+                data.arc = arc;
+            }
+
+            #[cfg(feature = "gpu")]
+            if let Ok(gpu) = collected.gpus {
+                data.gpu = gpu;
+            }
+        }
+    }
+}
@@ -1,7 +1,10 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 cfg_if::cfg_if! {
-    if #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))] {
+    if #[cfg(target_os = "linux")] {
+        pub mod linux;
+        pub use self::linux::*;
+    } else if #[cfg(any(target_os = "macos", target_os = "windows"))] {
         pub mod heim;
         pub use self::heim::*;
     } else if #[cfg(target_os = "freebsd")] {
@@ -10,7 +13,7 @@ cfg_if::cfg_if! {
     }
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MemHarvest {
     pub mem_total_in_kib: u64,
     pub mem_used_in_kib: u64,
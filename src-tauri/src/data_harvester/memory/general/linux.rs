@@ -0,0 +1,149 @@
+//! Data collection for memory on Linux, read from `/proc`.
+
+use std::path::Path;
+
+use fxhash::FxHashMap;
+
+use crate::data_harvester::harvester::under_root;
+use crate::data_harvester::memory::{MemCollect, MemHarvest};
+use crate::utils::error;
+
+pub async fn get_mem_data(root: &Path) -> MemCollect {
+    MemCollect {
+        ram: get_ram_data(root).await,
+        swap: get_swap_data(root).await,
+        #[cfg(feature = "zfs")]
+        arc: get_arc_data(root).await,
+        #[cfg(feature = "gpu")]
+        gpus: get_gpu_data().await,
+    }
+}
+
+/// The fields of `/proc/meminfo`, by name. All values are in KiB.
+pub fn read_meminfo(root: &Path) -> error::Result<FxHashMap<String, u64>> {
+    let meminfo = std::fs::read_to_string(under_root(root, "/proc/meminfo"))?;
+
+    Ok(meminfo
+        .lines()
+        .filter_map(|line| {
+            let (label, value) = line.split_once(':')?;
+            // Most are followed by a `kB` unit, but counts like `HugePages_Total` aren't.
+            let number = value.split_whitespace().next()?.parse().ok()?;
+            Some((label.to_string(), number))
+        })
+        .collect())
+}
+
+pub async fn get_ram_data(root: &Path) -> error::Result<Option<MemHarvest>> {
+    let meminfo = read_meminfo(root)?;
+    let field = |label: &str| meminfo.get(label).copied().unwrap_or(0);
+
+    let mem_total = field("MemTotal");
+    let mem_free = field("MemFree");
+    let buffers = field("Buffers");
+    let cached = field("Cached");
+    let shmem = field("Shmem");
+    let s_reclaimable = field("SReclaimable");
+
+    // Let's preface this by saying that memory usage calculations are... not straightforward.
+    // There are conflicting implementations everywhere.
+    //
+    // Now that we've added this preface (mainly for future reference), the current implementation below for usage
+    // is based on htop's calculation formula. See
+    // https://github.com/htop-dev/htop/blob/976c6123f41492aaf613b9d172eef1842fb7b0a3/linux/LinuxProcessList.c#L1584
+    // for implementation details as of writing.
+    //
+    // Another implementation, commonly used in other things, is to skip the shmem part of the calculation,
+    // which matches gopsutil and stuff like free.
+
+    let total = mem_total;
+    let cached_mem = cached + s_reclaimable - shmem;
+    let used_diff = mem_free + cached_mem + buffers;
+    let used = if total >= used_diff {
+        total - used_diff
+    } else {
+        total - mem_free
+    };
+
+    Ok(Some(mem_harvest(total, used)))
+}
+
+pub async fn get_swap_data(root: &Path) -> error::Result<Option<MemHarvest>> {
+    let meminfo = read_meminfo(root)?;
+    let field = |label: &str| meminfo.get(label).copied().unwrap_or(0);
+
+    let total = field("SwapTotal");
+    let used = total.saturating_sub(field("SwapFree"));
+
+    Ok(Some(mem_harvest(total, used)))
+}
+
+#[cfg(feature = "zfs")]
+pub async fn get_arc_data(root: &Path) -> error::Result<Option<MemHarvest>> {
+    let mut mem_arc = 0;
+    let mut mem_total = 0;
+    let mut zfs_keys_read: u8 = 0;
+    const ZFS_KEYS_NEEDED: u8 = 2;
+    use smol::fs::read_to_string;
+    let arcinfo = read_to_string(under_root(root, "/proc/spl/kstat/zfs/arcstats")).await?;
+    for line in arcinfo.lines() {
+        if let Some((label, value)) = line.split_once(' ') {
+            let to_write = match label {
+                "size" => &mut mem_arc,
+                "memory_all_bytes" => &mut mem_total,
+                _ => {
+                    continue;
+                }
+            };
+
+            if let Some((_type, number)) = value.trim_start().rsplit_once(' ') {
+                // Parse the value, remember it's in bytes!
+                if let Ok(number) = number.parse::<u64>() {
+                    *to_write = number;
+                    // We only need a few keys, so we can bail early.
+                    zfs_keys_read += 1;
+                    if zfs_keys_read == ZFS_KEYS_NEEDED {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Some(mem_harvest(mem_total / 1024, mem_arc / 1024)))
+}
+
+#[cfg(feature = "nvidia")]
+pub async fn get_gpu_data() -> error::Result<Option<Vec<(String, MemHarvest)>>> {
+    use crate::data_harvester::nvidia::NVML_DATA;
+    if let Ok(nvml) = &*NVML_DATA {
+        if let Ok(ngpu) = nvml.device_count() {
+            let mut results = Vec::with_capacity(ngpu as usize);
+            for i in 0..ngpu {
+                if let Ok(device) = nvml.device_by_index(i) {
+                    if let (Ok(name), Ok(mem)) = (device.name(), device.memory_info()) {
+                        // add device memory in bytes
+                        results.push((name, mem_harvest(mem.total / 1024, mem.used / 1024)));
+                    }
+                }
+            }
+            Ok(Some(results))
+        } else {
+            Ok(None)
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+fn mem_harvest(mem_total_in_kib: u64, mem_used_in_kib: u64) -> MemHarvest {
+    MemHarvest {
+        mem_total_in_kib,
+        mem_used_in_kib,
+        use_percent: if mem_total_in_kib == 0 {
+            None
+        } else {
+            Some(mem_used_in_kib as f64 / mem_total_in_kib as f64 * 100.0)
+        },
+    }
+}
@@ -1,12 +1,21 @@
 //! Data collection for network usage/IO.
 //!
-//! For Linux and macOS, this is handled by Heim.
+//! For Linux, this is read from `/proc/net/dev`.
+//! For macOS, this is handled by Heim.
 //! For Windows, this is handled by sysinfo.
 
-use serde::Serialize;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+
+use super::harvester::{HarvestContext, Harvester, SysRefresh};
+use super::{Data, Subsystem};
+use crate::utils::error;
 
 cfg_if::cfg_if! {
-    if #[cfg(any(target_os = "linux", target_os = "macos"))] {
+    if #[cfg(target_os = "linux")] {
+        pub mod linux;
+        pub use self::linux::*;
+    } else if #[cfg(target_os = "macos")] {
         pub mod heim;
         pub use self::heim::*;
     } else if #[cfg(any(target_os = "freebsd", target_os = "windows"))] {
@@ -15,7 +24,7 @@ cfg_if::cfg_if! {
     }
 }
 
-#[derive(Default, Clone, Debug, Serialize)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 /// All units in bits.
 pub struct NetworkHarvest {
     pub rx: u64,
@@ -30,3 +39,92 @@ impl NetworkHarvest {
         self.tx = 0;
     }
 }
+
+#[derive(Debug, Default)]
+pub struct NetworkHarvester {
+    total_rx: u64,
+    total_tx: u64,
+    network: Option<NetworkHarvest>,
+}
+
+impl Harvester for NetworkHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Network
+    }
+
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        if cfg!(target_os = "windows") {
+            &[SysRefresh::Networks]
+        } else {
+            &[]
+        }
+    }
+
+    fn init(&mut self, _sys: &mut ::sysinfo::System) -> error::Result<()> {
+        #[cfg(target_os = "windows")]
+        {
+            use ::sysinfo::SystemExt;
+
+            // Refresh network list once...
+            _sys.refresh_networks_list();
+        }
+
+        Ok(())
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            let prev_net_access_time = ctx.last_refresh(Subsystem::Network).unwrap_or(ctx.now);
+
+            let network = {
+                #[cfg(any(target_os = "windows", target_os = "freebsd"))]
+                {
+                    get_network_data(
+                        ctx.sys,
+                        prev_net_access_time,
+                        &mut self.total_rx,
+                        &mut self.total_tx,
+                        ctx.now,
+                    )
+                    .await?
+                }
+                #[cfg(target_os = "linux")]
+                {
+                    get_network_data(
+                        ctx.root,
+                        prev_net_access_time,
+                        &mut self.total_rx,
+                        &mut self.total_tx,
+                        ctx.now,
+                    )
+                    .await?
+                }
+                #[cfg(target_os = "macos")]
+                {
+                    get_network_data(
+                        prev_net_access_time,
+                        &mut self.total_rx,
+                        &mut self.total_tx,
+                        ctx.now,
+                    )
+                    .await?
+                }
+            };
+
+            if let Some(network) = &network {
+                self.total_rx = network.total_rx;
+                self.total_tx = network.total_tx;
+            }
+            self.network = network;
+
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.network = self.network.take();
+    }
+}
@@ -0,0 +1,58 @@
+//! Gets network data on Linux, from `/proc/net/dev`.
+
+use std::{path::Path, time::Instant};
+
+use super::NetworkHarvest;
+use crate::data_harvester::harvester::under_root;
+
+// TODO: Eventually make it so that this thing also takes individual usage into account, so we can show per-interface!
+pub async fn get_network_data(
+    root: &Path,
+    prev_net_access_time: Instant,
+    prev_net_rx: &mut u64,
+    prev_net_tx: &mut u64,
+    curr_time: Instant,
+) -> crate::utils::error::Result<Option<NetworkHarvest>> {
+    let dev = std::fs::read_to_string(under_root(root, "/proc/net/dev"))?;
+    let mut total_rx: u64 = 0;
+    let mut total_tx: u64 = 0;
+
+    // The first two lines are headers. After the interface name, there are 8 received counters
+    // followed by 8 transmitted ones, each starting with the number of bytes.
+    for line in dev.lines().skip(2) {
+        if let Some((_interface, counters)) = line.split_once(':') {
+            let counters: Vec<u64> = counters
+                .split_whitespace()
+                .map(|counter| counter.parse().unwrap_or(0))
+                .collect();
+            if let (Some(rx_bytes), Some(tx_bytes)) = (counters.first(), counters.get(8)) {
+                // TODO: Use bytes as the default instead, perhaps?
+                // Since you might have to do a double conversion (bytes -> bits -> bytes) in some cases;
+                // but if you stick to bytes, then in the bytes, case, you do no conversion, and in the bits case,
+                // you only do one conversion...
+                total_rx += rx_bytes * 8;
+                total_tx += tx_bytes * 8;
+            }
+        }
+    }
+
+    let elapsed_time = curr_time.duration_since(prev_net_access_time).as_secs_f64();
+
+    let (rx, tx) = if elapsed_time == 0.0 {
+        (0, 0)
+    } else {
+        (
+            ((total_rx.saturating_sub(*prev_net_rx)) as f64 / elapsed_time) as u64,
+            ((total_tx.saturating_sub(*prev_net_tx)) as f64 / elapsed_time) as u64,
+        )
+    };
+
+    *prev_net_rx = total_rx;
+    *prev_net_tx = total_tx;
+    Ok(Some(NetworkHarvest {
+        rx,
+        tx,
+        total_rx,
+        total_tx,
+    }))
+}
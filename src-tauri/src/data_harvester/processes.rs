@@ -28,11 +28,18 @@ cfg_if::cfg_if! {
     }
 }
 
-use serde::Serialize;
+use futures::future::LocalBoxFuture;
+#[cfg(target_os = "linux")]
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
 
+use super::cgroups::CgroupOwner;
+use super::harvester::{HarvestContext, Harvester, SysRefresh};
+use super::{Data, Subsystem};
+use crate::utils::error;
 use crate::Pid;
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProcessHarvest {
     /// The pid of the process.
     pub pid: Pid,
@@ -46,9 +53,31 @@ pub struct ProcessHarvest {
     /// Memory usage as a percentage.
     pub mem_usage_percent: f64,
 
-    /// Memory usage as bytes.
+    /// Memory usage as bytes. This is the resident set size, the memory actually in RAM.
     pub mem_usage_bytes: u64,
 
+    /// How much of the resident memory is shared with other processes, like libraries and shared
+    /// memory, in bytes. `None` where the platform doesn't say.
+    pub shared_mem_bytes: Option<u64>,
+
+    /// The proportional set size in bytes: the resident memory with each page shared with other
+    /// processes split evenly between them, so that, unlike `mem_usage_bytes`, it can be added up
+    /// over processes. Only read with [`CollectorOptions::proportional_memory_processes`], and only
+    /// on Linux.
+    ///
+    /// [`CollectorOptions::proportional_memory_processes`]: crate::data_harvester::CollectorOptions::proportional_memory_processes
+    pub pss_bytes: Option<u64>,
+
+    /// The unique set size in bytes: the resident memory that no other process shares, which is
+    /// what would be freed if it exited. Read along with `pss_bytes`.
+    pub uss_bytes: Option<u64>,
+
+    /// How much of the process' memory is swapped out, in bytes. Only on Linux.
+    pub swap_bytes: Option<u64>,
+
+    /// The size of the virtual address space in bytes, including what's mapped but not in RAM.
+    pub virtual_mem_bytes: u64,
+
     /// The name of the process.
     pub name: String,
 
@@ -70,6 +99,52 @@ pub struct ProcessHarvest {
     /// The current state of the process (e.g. zombie, asleep)
     pub process_state: (String, char),
 
+    /// When the process started, in milliseconds since the Unix epoch. Together with the pid, this
+    /// tells processes apart, as pids get reused.
+    pub start_time: u64,
+
+    /// How long the process had been running when it was read, in milliseconds.
+    #[serde(default)]
+    pub elapsed_millis: u64,
+
+    /// The time the process has spent on a CPU so far, in user and kernel mode, in milliseconds.
+    /// `None` where the platform doesn't say.
+    pub cpu_time_millis: Option<u64>,
+
+    /// From -20 for the highest priority to 19 for the lowest. `None` where the platform doesn't
+    /// say.
+    pub nice: Option<i64>,
+
+    /// The priority the scheduler gives the process, in the kernel's terms. On Linux, that's the
+    /// nice value plus 20 for most processes, and negative for real-time ones. `None` where the
+    /// platform doesn't say.
+    pub priority: Option<i64>,
+
+    /// How many threads the process has. `None` where the platform doesn't say.
+    pub thread_count: Option<u64>,
+
+    /// Each of the process' threads, ordered by thread id. Only listed for processes that they've
+    /// been asked for, with [`DataCollector::set_thread_collection`], and only on Linux.
+    ///
+    /// [`DataCollector::set_thread_collection`]: crate::data_harvester::DataCollector::set_thread_collection
+    pub threads: Option<Vec<ThreadHarvest>>,
+
+    /// The controlling terminal, like `pts/3`. `None` for daemons and kernel threads.
+    pub tty: Option<String>,
+
+    /// How many files, sockets and pipes the process has open. Only counted with
+    /// [`CollectorOptions::count_open_files`], as that's a directory listing for every process, and
+    /// only on Linux.
+    ///
+    /// [`CollectorOptions::count_open_files`]: crate::data_harvester::CollectorOptions::count_open_files
+    pub open_files: Option<u64>,
+
+    /// The path of the process' cgroup, like `/system.slice/nginx.service`. Only on Linux.
+    pub cgroup: Option<String>,
+
+    /// The container, systemd unit or login session the process belongs to, going by its cgroup.
+    pub owner: Option<CgroupOwner>,
+
     /// This is the *effective* user ID of the process. This is only used on Unix platforms.
     #[cfg(target_family = "unix")]
     pub uid: Option<libc::uid_t>,
@@ -77,7 +152,154 @@ pub struct ProcessHarvest {
     /// This is the process' user. This is only used on Unix platforms.
     #[cfg(target_family = "unix")]
     pub user: std::borrow::Cow<'static, str>,
-    // TODO: Additional fields
-    // pub rss_kb: u64,
-    // pub virt_kb: u64,
+}
+
+#[cfg(test)]
+impl ProcessHarvest {
+    /// A sleeping process run from `/usr/bin/<name>`, with nothing else set, for tests to fill in
+    /// what they check.
+    pub fn for_test(pid: Pid, name: &str) -> Self {
+        ProcessHarvest {
+            pid,
+            name: name.to_string(),
+            command: format!("/usr/bin/{}", name),
+            process_state: ("Sleeping".to_string(), 'S'),
+            ..ProcessHarvest::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ThreadHarvest {
+    /// The thread id. The process' main thread has the same id as the process.
+    pub tid: Pid,
+
+    pub name: String,
+
+    /// The current state of the thread, as for [`ProcessHarvest::process_state`].
+    pub state: (String, char),
+
+    /// CPU usage as a percentage, in the same terms as the process'. Zero on the first refresh
+    /// after the thread is first seen.
+    pub cpu_usage_percent: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct ProcessHarvester {
+    mem_total_kb: u64,
+    #[cfg(target_os = "linux")]
+    pid_mapping: FxHashMap<Pid, PrevProcDetails>,
+    #[cfg(target_os = "linux")]
+    prev_idle: f64,
+    #[cfg(target_os = "linux")]
+    prev_non_idle: f64,
+    #[cfg(target_family = "unix")]
+    user_table: UserTable,
+    processes: Option<Vec<ProcessHarvest>>,
+}
+
+impl Harvester for ProcessHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Processes
+    }
+
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        if cfg!(target_os = "linux") {
+            &[]
+        } else {
+            // Process CPU usage is relative to the overall CPU usage, so that needs refreshing too.
+            &[SysRefresh::Cpu, SysRefresh::Processes]
+        }
+    }
+
+    fn init(&mut self, _sys: &mut sysinfo::System) -> error::Result<()> {
+        // On Linux, this is read on each refresh instead, as that's when the root is known.
+        #[cfg(not(target_os = "linux"))]
+        {
+            use sysinfo::SystemExt;
+
+            _sys.refresh_memory();
+            self.mem_total_kb = _sys.total_memory();
+        }
+
+        Ok(())
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            let mut process_list = {
+                #[cfg(target_os = "linux")]
+                {
+                    use crate::data_harvester::{cpu::read_cpu_times, memory::read_meminfo};
+
+                    let normalize_cpu = if ctx.options.unnormalized_cpu {
+                        read_cpu_times(ctx.root)
+                            .map(
+                                |times| CpuUsageStrategy::NonNormalized(times.per_cpu.len() as f64),
+                            )
+                            .unwrap_or(CpuUsageStrategy::Normalized)
+                    } else {
+                        CpuUsageStrategy::Normalized
+                    };
+                    let time_difference_in_secs = ctx
+                        .last_refresh(Subsystem::Processes)
+                        .map_or(0, |last| ctx.now.duration_since(last).as_secs());
+
+                    self.mem_total_kb = read_meminfo(ctx.root)
+                        .ok()
+                        .and_then(|meminfo| meminfo.get("MemTotal").copied())
+                        .unwrap_or(1);
+
+                    get_process_data(
+                        ctx.root,
+                        &mut self.prev_idle,
+                        &mut self.prev_non_idle,
+                        &mut self.pid_mapping,
+                        ctx.options.use_current_cpu_total,
+                        normalize_cpu,
+                        time_difference_in_secs,
+                        self.mem_total_kb,
+                        ctx.thread_pids,
+                        ctx.options.count_open_files,
+                        ctx.options.proportional_memory_processes,
+                        &mut self.user_table,
+                    )?
+                }
+                #[cfg(all(not(target_os = "linux"), target_family = "unix"))]
+                {
+                    get_process_data(
+                        ctx.sys,
+                        ctx.options.use_current_cpu_total,
+                        ctx.options.unnormalized_cpu,
+                        self.mem_total_kb,
+                        &mut self.user_table,
+                    )?
+                }
+                #[cfg(not(target_family = "unix"))]
+                {
+                    get_process_data(
+                        ctx.sys,
+                        ctx.options.use_current_cpu_total,
+                        ctx.options.unnormalized_cpu,
+                        self.mem_total_kb,
+                    )?
+                }
+            };
+
+            // NB: To avoid duplicate sorts on rerenders/events, we sort the processes by PID here.
+            // We also want to avoid re-sorting *again* later on if we're sorting by PID, since we already
+            // did it here!
+            process_list.sort_unstable_by_key(|p| p.pid);
+            self.processes = Some(process_list);
+
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.list_of_processes = self.processes.take();
+    }
 }
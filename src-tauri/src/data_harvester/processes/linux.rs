@@ -2,13 +2,18 @@
 
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 
 use fxhash::{FxHashMap, FxHashSet};
 use procfs::process::{Process, Stat};
+use serde::{Deserialize, Serialize};
 use sysinfo::ProcessStatus;
 
-use super::{ProcessHarvest, UserTable};
+use super::{ProcessHarvest, ThreadHarvest, UserTable};
+use crate::data_harvester::cgroups::{owner_of, parse_cgroup_file};
 use crate::data_harvester::cpu::Point;
+use crate::data_harvester::harvester::under_root;
+use crate::data_harvester::unix_time_millis;
 use crate::utils::error::{self, ToeError};
 use crate::Pid;
 
@@ -21,6 +26,8 @@ pub struct PrevProcDetails {
     total_read_bytes: u64,
     total_write_bytes: u64,
     cpu_time: u64,
+    /// The CPU time of each thread, by thread id, if the threads are being listed.
+    thread_cpu_times: FxHashMap<Pid, u64>,
 }
 
 fn calculate_idle_values(line: &str) -> Point {
@@ -56,10 +63,14 @@ struct CpuUsage {
     cpu_fraction: f64,
 }
 
-fn cpu_usage_calculation(prev_idle: &mut f64, prev_non_idle: &mut f64) -> error::Result<CpuUsage> {
+fn cpu_usage_calculation(
+    root: &Path,
+    prev_idle: &mut f64,
+    prev_non_idle: &mut f64,
+) -> error::Result<CpuUsage> {
     let (idle, non_idle) = {
         // From SO answer: https://stackoverflow.com/a/23376195
-        let mut reader = BufReader::new(File::open("/proc/stat")?);
+        let mut reader = BufReader::new(File::open(under_root(root, "/proc/stat"))?);
         let mut first_line = String::new();
         reader.read_line(&mut first_line)?;
 
@@ -94,6 +105,40 @@ fn cpu_usage_calculation(prev_idle: &mut f64, prev_non_idle: &mut f64) -> error:
     })
 }
 
+/// When the system booted, in seconds since the Unix epoch.
+pub fn read_boot_time(root: &Path) -> error::Result<u64> {
+    let stat = std::fs::read_to_string(under_root(root, "/proc/stat"))?;
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|boot_time| boot_time.trim().parse().ok())
+        .ok_or_else(|| ToeError::GenericError("Could not find the boot time.".to_string()))
+}
+
+/// Converts clock ticks, which `/proc` has times in, to milliseconds.
+fn ticks_to_millis(ticks: u64) -> u64 {
+    let ticks_per_second = procfs::ticks_per_second().unwrap_or(100).max(1);
+    ticks * 1000 / ticks_per_second
+}
+
+/// When the process started, in milliseconds since the Unix epoch. `boot_time` is as from
+/// [`read_boot_time`].
+pub fn start_time_millis(stat: &Stat, boot_time: u64) -> u64 {
+    boot_time * 1000 + ticks_to_millis(stat.starttime)
+}
+
+/// The name of the terminal with the device number `(major, minor)`, the way `ps` shows it.
+fn tty_name((major, minor): (i32, i32)) -> Option<String> {
+    let name = match major {
+        0 => return None,
+        // Pseudo-terminals, which terminal emulators and ssh use.
+        136..=143 => format!("pts/{}", (major - 136) * 256 + minor),
+        4 if minor < 64 => format!("tty{}", minor),
+        4 => format!("ttyS{}", minor - 64),
+        _ => format!("{}:{}", major, minor),
+    };
+    Some(name)
+}
+
 /// Returns the usage and a new set of process times.
 ///
 /// NB: cpu_fraction should be represented WITHOUT the x100 factor!
@@ -117,7 +162,130 @@ fn get_linux_cpu_usage(
     }
 }
 
+/// Lists the threads of `process`, with their CPU usage since the last time they were listed.
+fn read_threads(
+    process: &Process,
+    prev_thread_cpu_times: &mut FxHashMap<Pid, u64>,
+    cpu_usage: f64,
+    cpu_fraction: f64,
+    use_current_cpu_total: bool,
+) -> error::Result<Vec<ThreadHarvest>> {
+    let mut thread_cpu_times = FxHashMap::default();
+    let mut threads: Vec<ThreadHarvest> = process
+        .tasks()?
+        .flatten()
+        // A thread can exit between listing it and reading its stat.
+        .filter_map(|task| Some((task.tid, task.stat().ok()?)))
+        .map(|(tid, stat)| {
+            let (cpu_usage_percent, new_thread_times) = match prev_thread_cpu_times.get(&tid) {
+                Some(prev_thread_times) => get_linux_cpu_usage(
+                    &stat,
+                    cpu_usage,
+                    cpu_fraction,
+                    *prev_thread_times,
+                    use_current_cpu_total,
+                ),
+                // Measured against zero, this would be all the time the thread ever ran.
+                None => (0.0, stat.utime + stat.stime),
+            };
+            thread_cpu_times.insert(tid, new_thread_times);
+
+            ThreadHarvest {
+                tid,
+                state: (ProcessStatus::from(stat.state).to_string(), stat.state),
+                name: stat.comm,
+                cpu_usage_percent,
+            }
+        })
+        .collect();
+    *prev_thread_cpu_times = thread_cpu_times;
+
+    threads.sort_unstable_by_key(|thread| thread.tid);
+    Ok(threads)
+}
+
+/// How many file descriptors the process `pid` has open. `None` if they can't be listed, which
+/// needs the same permissions as reading its IO counters.
+fn count_open_files_of(root: &Path, pid: Pid) -> Option<u64> {
+    // Rather than `Process::fd_count`, which counts `.` and `..` too.
+    let fds = std::fs::read_dir(under_root(root, format!("/proc/{}/fd", pid))).ok()?;
+    Some(fds.count() as u64)
+}
+
+/// A process' memory from `/proc/<pid>/smaps_rollup`, with the pages it shares with other
+/// processes told apart from its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRollup {
+    /// Every page it has in memory, the same as `mem_usage_bytes`. Shared libraries are counted in
+    /// full for every process that maps them.
+    pub rss_bytes: u64,
+    /// Each shared page split evenly between the processes that map it, so that adding this up
+    /// over processes gives what they use between them.
+    pub pss_bytes: u64,
+    /// Only the pages no other process maps, which is what would be freed if it exited.
+    pub uss_bytes: u64,
+    pub swap_bytes: u64,
+}
+
+/// Parses the contents of `/proc/<pid>/smaps_rollup`, which has a line for the range of addresses
+/// and then lines like `Pss:  1234 kB`.
+pub fn parse_smaps_rollup(contents: &str) -> MemoryRollup {
+    let mut rollup = MemoryRollup::default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(kib) = value
+            .trim()
+            .strip_suffix("kB")
+            .and_then(|kib| kib.trim().parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let bytes = kib * 1024;
+        match key {
+            "Rss" => rollup.rss_bytes = bytes,
+            "Pss" => rollup.pss_bytes = bytes,
+            "Private_Clean" | "Private_Dirty" | "Private_Hugetlb" => rollup.uss_bytes += bytes,
+            "Swap" => rollup.swap_bytes = bytes,
+            _ => {}
+        }
+    }
+    rollup
+}
+
+/// Looks up the name of `uid`. Under another root, that root's own `/etc/passwd` is used instead,
+/// as its users needn't be this machine's.
+fn get_username(
+    root: &Path,
+    uid: libc::uid_t,
+    user_table: &mut UserTable,
+) -> error::Result<String> {
+    if root == Path::new("/") {
+        return user_table.get_uid_to_username_mapping(uid);
+    } else if let Some(user) = user_table.uid_user_mapping.get(&uid) {
+        return Ok(user.clone());
+    }
+
+    let passwd = std::fs::read_to_string(under_root(root, "/etc/passwd"))?;
+    let username = passwd
+        .lines()
+        .find_map(|line| {
+            // name:password:uid:gid:...
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let entry_uid = fields.nth(1)?.parse::<libc::uid_t>().ok()?;
+            (entry_uid == uid).then(|| name.to_string())
+        })
+        .ok_or_else(|| ToeError::QueryError("Missing passwd".into()))?;
+    user_table.uid_user_mapping.insert(uid, username.clone());
+
+    Ok(username)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn read_proc(
+    root: &Path,
     prev_proc: &PrevProcDetails,
     process: &Process,
     cpu_usage: f64,
@@ -125,8 +293,11 @@ fn read_proc(
     use_current_cpu_total: bool,
     time_difference_in_secs: u64,
     mem_total_kb: u64,
+    boot_time: u64,
+    list_threads: bool,
+    count_open_files: bool,
     user_table: &mut UserTable,
-) -> error::Result<(ProcessHarvest, u64)> {
+) -> error::Result<(ProcessHarvest, u64, FxHashMap<Pid, u64>)> {
     let stat = process.stat()?;
     let (command, name) = {
         let truncated_name = stat.comm.as_str();
@@ -174,6 +345,10 @@ fn read_proc(
     let mem_usage_bytes = stat.rss_bytes()?;
     let mem_usage_kb = mem_usage_bytes / 1024;
     let mem_usage_percent = mem_usage_kb as f64 / mem_total_kb as f64 * 100.0;
+    let shared_mem_bytes = process
+        .statm()
+        .ok()
+        .and_then(|statm| Some(statm.shared * procfs::page_size().ok()?));
 
     // This can fail if permission is denied!
     let (total_read_bytes, total_write_bytes, read_bytes_per_sec, write_bytes_per_sec) =
@@ -203,7 +378,33 @@ fn read_proc(
             (0, 0, 0, 0)
         };
 
-    let uid = process.uid()?;
+    // If the threads can't be read, the process is still listed without them.
+    let mut thread_cpu_times = prev_proc.thread_cpu_times.clone();
+    let threads = if list_threads {
+        read_threads(
+            process,
+            &mut thread_cpu_times,
+            cpu_usage,
+            cpu_fraction,
+            use_current_cpu_total,
+        )
+        .ok()
+    } else {
+        thread_cpu_times.clear();
+        None
+    };
+
+    // Missing if the kernel was built without cgroups.
+    let cgroup = std::fs::read_to_string(under_root(root, format!("/proc/{}/cgroup", process.pid)))
+        .ok()
+        .and_then(|contents| parse_cgroup_file(&contents));
+    let owner = cgroup.as_deref().and_then(owner_of);
+
+    let status = process.status()?;
+    // Read from `status` rather than taken from the owner of the process' directory, which would
+    // be whoever copied it if this is a captured tree.
+    let uid = status.euid;
+    let start_time = start_time_millis(&stat, boot_time);
 
     Ok((
         ProcessHarvest {
@@ -212,6 +413,12 @@ fn read_proc(
             cpu_usage_percent,
             mem_usage_percent,
             mem_usage_bytes,
+            shared_mem_bytes,
+            // Read for the processes that need it once they've all been read.
+            pss_bytes: None,
+            uss_bytes: None,
+            swap_bytes: status.vmswap.map(|kib| kib * 1024),
+            virtual_mem_bytes: stat.vsize,
             name,
             command,
             read_bytes_per_sec,
@@ -219,16 +426,49 @@ fn read_proc(
             total_read_bytes,
             total_write_bytes,
             process_state,
+            start_time,
+            elapsed_millis: unix_time_millis().saturating_sub(start_time),
+            cpu_time_millis: Some(ticks_to_millis(stat.utime + stat.stime)),
+            nice: Some(stat.nice),
+            priority: Some(stat.priority),
+            thread_count: u64::try_from(stat.num_threads).ok(),
+            threads,
+            tty: tty_name(stat.tty_nr()),
+            open_files: count_open_files
+                .then(|| count_open_files_of(root, process.pid))
+                .flatten(),
+            cgroup,
+            owner,
             uid: Some(uid),
-            user: user_table
-                .get_uid_to_username_mapping(uid)
+            user: get_username(root, uid, user_table)
                 .map(Into::into)
                 .unwrap_or_else(|_| "N/A".into()),
         },
         new_process_times,
+        thread_cpu_times,
     ))
 }
 
+/// Reads the PSS and USS of the `count` processes with the most resident memory. It means going
+/// through all of each one's memory mappings, which is too slow to do for every process.
+fn read_memory_rollups(root: &Path, processes: &mut [ProcessHarvest], count: usize) {
+    let mut largest: Vec<&mut ProcessHarvest> = processes.iter_mut().collect();
+    largest.sort_by_key(|process| std::cmp::Reverse(process.mem_usage_bytes));
+    for process in largest.into_iter().take(count) {
+        let path = under_root(root, format!("/proc/{}/smaps_rollup", process.pid));
+        // It can't be read for other users' processes, and it's empty for kernel threads.
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        if contents.is_empty() {
+            continue;
+        }
+        let rollup = parse_smaps_rollup(&contents);
+        process.pss_bytes = Some(rollup.pss_bytes);
+        process.uss_bytes = Some(rollup.uss_bytes);
+    }
+}
+
 /// How to calculate CPU usage.
 pub enum CpuUsageStrategy {
     /// Normalized means the displayed usage percentage is divided over the number of CPU cores.
@@ -242,7 +482,9 @@ pub enum CpuUsageStrategy {
     NonNormalized(f64),
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn get_process_data(
+    root: &Path,
     prev_idle: &mut f64,
     prev_non_idle: &mut f64,
     pid_mapping: &mut FxHashMap<Pid, PrevProcDetails>,
@@ -250,14 +492,15 @@ pub fn get_process_data(
     normalization: CpuUsageStrategy,
     time_difference_in_secs: u64,
     mem_total_kb: u64,
+    thread_pids: &FxHashSet<Pid>,
+    count_open_files: bool,
+    proportional_memory_processes: usize,
     user_table: &mut UserTable,
 ) -> crate::utils::error::Result<Vec<ProcessHarvest>> {
-    // TODO: [PROC THREADS] Add threads
-
     if let Ok(CpuUsage {
         mut cpu_usage,
         cpu_fraction,
-    }) = cpu_usage_calculation(prev_idle, prev_non_idle)
+    }) = cpu_usage_calculation(root, prev_idle, prev_non_idle)
     {
         if let CpuUsageStrategy::NonNormalized(num_cores) = normalization {
             // Note we *divide* here because the later calculation divides `cpu_usage` - in effect,
@@ -265,28 +508,36 @@ pub fn get_process_data(
             cpu_usage /= num_cores;
         }
 
+        let boot_time = read_boot_time(root)?;
         let mut pids_to_clear: FxHashSet<Pid> = pid_mapping.keys().cloned().collect();
 
-        let process_vector: Vec<ProcessHarvest> = std::fs::read_dir("/proc")?
+        let mut process_vector: Vec<ProcessHarvest> = std::fs::read_dir(under_root(root, "/proc"))?
             .filter_map(|dir| {
                 if let Ok(dir) = dir {
                     if let Ok(pid) = dir.file_name().to_string_lossy().trim().parse::<Pid>() {
-                        let Ok(process) = Process::new(pid) else {
+                        let Ok(process) = Process::new_with_root(dir.path()) else {
                             return None;
                         };
                         let prev_proc_details = pid_mapping.entry(pid).or_default();
 
-                        if let Ok((process_harvest, new_process_times)) = read_proc(
-                            prev_proc_details,
-                            &process,
-                            cpu_usage,
-                            cpu_fraction,
-                            use_current_cpu_total,
-                            time_difference_in_secs,
-                            mem_total_kb,
-                            user_table,
-                        ) {
+                        if let Ok((process_harvest, new_process_times, thread_cpu_times)) =
+                            read_proc(
+                                root,
+                                prev_proc_details,
+                                &process,
+                                cpu_usage,
+                                cpu_fraction,
+                                use_current_cpu_total,
+                                time_difference_in_secs,
+                                mem_total_kb,
+                                boot_time,
+                                thread_pids.contains(&pid),
+                                count_open_files,
+                                user_table,
+                            )
+                        {
                             prev_proc_details.cpu_time = new_process_times;
+                            prev_proc_details.thread_cpu_times = thread_cpu_times;
                             prev_proc_details.total_read_bytes = process_harvest.total_read_bytes;
                             prev_proc_details.total_write_bytes = process_harvest.total_write_bytes;
 
@@ -304,6 +555,10 @@ pub fn get_process_data(
             pid_mapping.remove(pid);
         });
 
+        if proportional_memory_processes > 0 {
+            read_memory_rollups(root, &mut process_vector, proportional_memory_processes);
+        }
+
         Ok(process_vector)
     } else {
         Err(ToeError::GenericError(
@@ -354,4 +609,42 @@ mod tests {
             "Failed to properly calculate idle/non-idle for /proc/stat CPU with 10 values"
         );
     }
+
+    #[test]
+    fn test_tty_name() {
+        assert_eq!(tty_name((0, 0)), None);
+        assert_eq!(tty_name((136, 3)), Some("pts/3".to_string()));
+        assert_eq!(tty_name((137, 1)), Some("pts/257".to_string()));
+        assert_eq!(tty_name((4, 1)), Some("tty1".to_string()));
+        assert_eq!(tty_name((4, 65)), Some("ttyS1".to_string()));
+    }
+
+    #[test]
+    fn test_read_threads() {
+        let (started, has_started) = std::sync::mpsc::channel::<()>();
+        let (stop, stopped) = std::sync::mpsc::channel::<()>();
+        let worker = std::thread::Builder::new()
+            .name("toerings-worker".to_string())
+            .spawn(move || {
+                // The thread names itself once it's running.
+                started.send(()).unwrap();
+                stopped.recv()
+            })
+            .unwrap();
+        has_started.recv().unwrap();
+
+        let process = Process::myself().unwrap();
+        let mut prev_thread_cpu_times = FxHashMap::default();
+        let threads = read_threads(&process, &mut prev_thread_cpu_times, 1.0, 1.0, false).unwrap();
+        assert_eq!(threads[0].tid, process.pid);
+        assert!(threads
+            .iter()
+            .any(|thread| thread.name == "toerings-worker"));
+        // Nothing to measure against yet.
+        assert!(threads.iter().all(|thread| thread.cpu_usage_percent == 0.0));
+        assert_eq!(prev_thread_cpu_times.len(), threads.len());
+
+        drop(stop);
+        let _ = worker.join().unwrap();
+    }
 }
@@ -72,12 +72,28 @@ pub fn get_process_data(
                 0.0
             },
             mem_usage_bytes: process_val.memory(),
+            shared_mem_bytes: None,
+            pss_bytes: None,
+            uss_bytes: None,
+            swap_bytes: None,
+            virtual_mem_bytes: process_val.virtual_memory(),
             cpu_usage_percent: process_cpu_usage,
             read_bytes_per_sec: disk_usage.read_bytes,
             write_bytes_per_sec: disk_usage.written_bytes,
             total_read_bytes: disk_usage.total_read_bytes,
             total_write_bytes: disk_usage.total_written_bytes,
             process_state,
+            start_time: process_val.start_time() * 1000,
+            elapsed_millis: process_val.run_time() * 1000,
+            cpu_time_millis: None,
+            nice: None,
+            priority: None,
+            thread_count: None,
+            threads: None,
+            tty: None,
+            open_files: None,
+            cgroup: None,
+            owner: None,
         });
     }
 
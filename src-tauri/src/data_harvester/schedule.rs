@@ -0,0 +1,162 @@
+//! How often each part of [`Data`](super::Data) gets refreshed.
+
+use std::{str::FromStr, time::Duration};
+
+use fxhash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+/// The parts of a snapshot that are refreshed independently of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Cpu,
+    Processes,
+    Temperature,
+    Network,
+    Memory,
+    Disks,
+    Io,
+    Batteries,
+    /// Containers and systemd slices, from their cgroups.
+    Containers,
+    /// Uptime, hostname, kernel and OS versions, and the local IP.
+    Host,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 10] = [
+        Subsystem::Cpu,
+        Subsystem::Processes,
+        Subsystem::Temperature,
+        Subsystem::Network,
+        Subsystem::Memory,
+        Subsystem::Disks,
+        Subsystem::Io,
+        Subsystem::Batteries,
+        Subsystem::Containers,
+        Subsystem::Host,
+    ];
+
+    /// The name used for this subsystem in the config file and in [`Data`](super::Data).
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Cpu => "cpu",
+            Subsystem::Processes => "processes",
+            Subsystem::Temperature => "temperature",
+            Subsystem::Network => "network",
+            Subsystem::Memory => "memory",
+            Subsystem::Disks => "disks",
+            Subsystem::Io => "io",
+            Subsystem::Batteries => "batteries",
+            Subsystem::Containers => "containers",
+            Subsystem::Host => "host",
+        }
+    }
+
+    /// The fields of [`Data`](super::Data) that this subsystem fills in.
+    pub fn fields(self) -> &'static [&'static str] {
+        match self {
+            Subsystem::Cpu => &["cpu", "load_avg"],
+            Subsystem::Processes => &["list_of_processes"],
+            Subsystem::Temperature => &["temperature_sensors"],
+            Subsystem::Network => &["network"],
+            Subsystem::Memory => &["memory", "swap", "arc", "gpu"],
+            Subsystem::Disks => &["disks"],
+            Subsystem::Io => &["io"],
+            Subsystem::Batteries => &["list_of_batteries"],
+            Subsystem::Containers => &["containers"],
+            Subsystem::Host => &[
+                "uptime",
+                "hostname",
+                "kernel_name",
+                "kernel_version",
+                "os_version",
+                "local_ip",
+            ],
+        }
+    }
+}
+
+impl FromStr for Subsystem {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Subsystem::ALL
+            .into_iter()
+            .find(|subsystem| subsystem.name() == name)
+            .ok_or_else(|| format!("unknown subsystem {:?}", name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubsystemSchedule {
+    pub enabled: bool,
+    pub interval: Duration,
+}
+
+impl SubsystemSchedule {
+    pub const fn every(interval: Duration) -> Self {
+        SubsystemSchedule {
+            enabled: true,
+            interval,
+        }
+    }
+}
+
+/// The per-subsystem refresh intervals. The collection thread ticks at the shortest enabled
+/// interval, so longer intervals are effectively rounded up to a multiple of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectionSchedule {
+    subsystems: FxHashMap<Subsystem, SubsystemSchedule>,
+}
+
+/// Used to pace the collection thread when every subsystem is disabled.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+impl Default for CollectionSchedule {
+    fn default() -> Self {
+        let subsystems = Subsystem::ALL
+            .iter()
+            .map(|&subsystem| {
+                let interval = match subsystem {
+                    // Scanning every process is most of the work of a refresh.
+                    Subsystem::Processes => Duration::from_secs(3),
+                    // Usage barely moves from one second to the next, and it's fairly expensive to
+                    // query for every mount.
+                    Subsystem::Disks => Duration::from_secs(30),
+                    _ => DEFAULT_INTERVAL,
+                };
+                (subsystem, SubsystemSchedule::every(interval))
+            })
+            .collect();
+
+        CollectionSchedule { subsystems }
+    }
+}
+
+impl CollectionSchedule {
+    pub fn get(&self, subsystem: Subsystem) -> SubsystemSchedule {
+        self.subsystems
+            .get(&subsystem)
+            .copied()
+            .unwrap_or(SubsystemSchedule::every(DEFAULT_INTERVAL))
+    }
+
+    pub fn set(&mut self, subsystem: Subsystem, schedule: SubsystemSchedule) {
+        self.subsystems.insert(subsystem, schedule);
+    }
+
+    pub fn is_enabled(&self, subsystem: Subsystem) -> bool {
+        self.get(subsystem).enabled
+    }
+
+    /// The shortest interval among the enabled subsystems.
+    pub fn tick_interval(&self) -> Duration {
+        self.subsystems
+            .values()
+            .filter(|schedule| schedule.enabled)
+            .map(|schedule| schedule.interval)
+            .min()
+            .unwrap_or(DEFAULT_INTERVAL)
+    }
+}
@@ -1,7 +1,7 @@
 //! Data collection for temperature metrics.
 //!
-//! For Linux and macOS, this is handled by Heim.
-//! For Windows, this is handled by sysinfo.
+//! For Linux, this is read from `/sys`.
+//! For macOS, Windows and FreeBSD, this is handled by sysinfo.
 
 cfg_if::cfg_if! {
     if #[cfg(target_os = "linux")] {
@@ -16,10 +16,72 @@ cfg_if::cfg_if! {
 #[cfg(feature = "nvidia")]
 pub mod nvidia;
 
-use serde::Serialize;
+use futures::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, Clone, Serialize)]
+use super::harvester::{HarvestContext, Harvester, SysRefresh};
+use super::{Data, Subsystem};
+use crate::utils::error;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct TempHarvest {
     pub name: String,
     pub temperature: f32,
 }
+
+#[derive(Debug, Default)]
+pub struct TemperatureHarvester {
+    sensors: Option<Vec<TempHarvest>>,
+}
+
+impl Harvester for TemperatureHarvester {
+    fn subsystem(&self) -> Subsystem {
+        Subsystem::Temperature
+    }
+
+    fn sys_refreshes(&self) -> &'static [SysRefresh] {
+        if cfg!(target_os = "linux") {
+            &[]
+        } else {
+            &[SysRefresh::Components]
+        }
+    }
+
+    fn init(&mut self, _sys: &mut ::sysinfo::System) -> error::Result<()> {
+        #[cfg(not(target_os = "linux"))]
+        {
+            use ::sysinfo::SystemExt;
+
+            // TODO: Would be good to get this running on a timer instead...?
+            // Refresh components list once...
+            _sys.refresh_components_list();
+        }
+
+        Ok(())
+    }
+
+    fn refresh<'a>(
+        &'a mut self,
+        ctx: &'a HarvestContext<'a>,
+    ) -> LocalBoxFuture<'a, error::Result<()>> {
+        Box::pin(async move {
+            #[cfg(not(target_os = "linux"))]
+            let sensors = get_temperature_data(ctx.sys)?;
+            #[cfg(target_os = "linux")]
+            let sensors = get_temperature_data(ctx.root)?;
+
+            self.sensors = sensors.map(|sensors| {
+                sensors
+                    .into_iter()
+                    .filter(|sensor| ctx.filters.keep_temperature(&sensor.name))
+                    .collect()
+            });
+
+            Ok(())
+        })
+    }
+
+    fn output(&mut self, data: &mut Data) {
+        data.temperature_sensors = self.sensors.take();
+    }
+}
@@ -5,6 +5,7 @@ use std::{fs, path::Path};
 use anyhow::{anyhow, Result};
 
 use super::TempHarvest;
+use crate::data_harvester::harvester::under_root;
 
 /// Get temperature sensors from the linux sysfs interface `/sys/class/hwmon`.
 /// See [here](https://www.kernel.org/doc/Documentation/ABI/testing/sysfs-class-hwmon) for
@@ -21,9 +22,9 @@ use super::TempHarvest;
 /// the device is already in ACPI D0. This has the notable issue that
 /// once this happens, the device will be *kept* on through the sensor
 /// reading, and not be able to re-enter ACPI D3cold.
-fn get_from_hwmon() -> Result<Vec<TempHarvest>> {
+fn get_from_hwmon(root: &Path) -> Result<Vec<TempHarvest>> {
     let mut temperature_vec: Vec<TempHarvest> = vec![];
-    let path = Path::new("/sys/class/hwmon");
+    let path = under_root(root, "/sys/class/hwmon");
 
     // NOTE: Technically none of this is async, *but* sysfs is in memory,
     // so in theory none of this should block if we're slightly careful.
@@ -36,8 +37,7 @@ fn get_from_hwmon() -> Result<Vec<TempHarvest>> {
     // and meaning no sensors have to be hidden depending on `power_state`
     //
     // It would probably be more ideal to use a proper async runtime..
-    for entry in path.read_dir()? {
-        let file = entry?;
+    for file in sorted_dir(&path)? {
         let mut file_path = file.path();
 
         // hwmon includes many sensors, we only want ones with at least one temperature sensor
@@ -81,8 +81,7 @@ fn get_from_hwmon() -> Result<Vec<TempHarvest>> {
         };
 
         // Enumerate the devices temperature sensors
-        for entry in file_path.read_dir()? {
-            let file = entry?;
+        for file in sorted_dir(&file_path)? {
             let name = file.file_name();
             // This should always be ASCII
             let name = name
@@ -148,8 +147,8 @@ fn get_from_hwmon() -> Result<Vec<TempHarvest>> {
 
             let name = match (&human_hwmon_name, &temp_label) {
                 (Some(name), Some(label)) => format!("{}: {}", name.trim(), label.trim()),
-                (None, Some(label)) => label.to_string(),
-                (Some(name), None) => name.to_string(),
+                (None, Some(label)) => label.trim().to_string(),
+                (Some(name), None) => name.trim().to_string(),
                 (None, None) => String::default(),
             };
 
@@ -181,11 +180,10 @@ fn get_from_hwmon() -> Result<Vec<TempHarvest>> {
 /// Gets data from `/sys/class/thermal/thermal_zone*`. This should only be used if
 /// [`get_from_hwmon`] doesn't return anything. See
 /// [here](https://www.kernel.org/doc/Documentation/ABI/testing/sysfs-class-thermal) for details.
-fn get_from_thermal_zone() -> Result<Vec<TempHarvest>> {
+fn get_from_thermal_zone(root: &Path) -> Result<Vec<TempHarvest>> {
     let mut temperatures = vec![];
-    let path = Path::new("/sys/class/thermal");
-    for entry in path.read_dir()? {
-        let file = entry?;
+    let path = under_root(root, "/sys/class/thermal");
+    for file in sorted_dir(&path)? {
         if file
             .file_name()
             .to_string_lossy()
@@ -212,13 +210,21 @@ fn get_from_thermal_zone() -> Result<Vec<TempHarvest>> {
     Ok(temperatures)
 }
 
+/// The entries of a directory, sorted by name so that sensors are listed in the same order whatever
+/// the filesystem.
+fn sorted_dir(path: &Path) -> Result<Vec<fs::DirEntry>> {
+    let mut entries = path.read_dir()?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    Ok(entries)
+}
+
 /// Gets temperature sensors and data.
-pub fn get_temperature_data() -> Result<Option<Vec<TempHarvest>>> {
-    let mut temperature_vec: Vec<TempHarvest> = get_from_hwmon()?;
+pub fn get_temperature_data(root: &Path) -> Result<Option<Vec<TempHarvest>>> {
+    let mut temperature_vec: Vec<TempHarvest> = get_from_hwmon(root)?;
 
     if temperature_vec.is_empty() {
         // If it's empty, fall back to checking `thermal_zone*`.
-        temperature_vec = get_from_thermal_zone()?;
+        temperature_vec = get_from_thermal_zone(root)?;
     }
 
     #[cfg(feature = "nvidia")]
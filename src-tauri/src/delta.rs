@@ -0,0 +1,228 @@
+//! Sends the frontend a full snapshot once, and then only what changed from one snapshot to the
+//! next. Most of a snapshot is the process list, and most processes look the same from one second
+//! to the next, so this keeps each update down to a fraction of the size.
+//!
+//! Every update has a sequence number one higher than the last. If the frontend sees a gap, it
+//! asks for the full snapshot again with `get_latest_data`.
+
+use fxhash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::data_harvester::Data;
+use crate::Pid;
+
+/// A serialized snapshot or process, by field name.
+type Fields = Map<String, Value>;
+
+/// The payload of the `dataCollected` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DataUpdate {
+    Full {
+        seq: u64,
+        data: Box<Data>,
+    },
+    /// The changes since the update numbered `seq - 1`.
+    Delta {
+        seq: u64,
+        /// The fields of [`Data`] that changed, with their new values. `list_of_processes` is only
+        /// here when the list was turned on or off, as otherwise its changes are in `processes`.
+        changed: Fields,
+        processes: ProcessesDelta,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProcessesDelta {
+    /// Processes that weren't in the last snapshot, in full.
+    pub added: Vec<Fields>,
+    pub removed: Vec<Pid>,
+    /// The `pid` of each process that changed, with only the fields that changed.
+    pub changed: Vec<Fields>,
+}
+
+impl ProcessesDelta {
+    fn between(previous: &FxHashMap<Pid, Fields>, current: &[(Pid, Fields)]) -> Self {
+        let mut delta = ProcessesDelta::default();
+        for (pid, process) in current {
+            let Some(previous) = previous.get(pid) else {
+                delta.added.push(process.clone());
+                continue;
+            };
+            let changed = changed_fields(previous, process);
+            if !changed.is_empty() {
+                let mut changed_process = Fields::new();
+                changed_process.insert("pid".to_string(), Value::from(*pid));
+                changed_process.extend(changed);
+                delta.changed.push(changed_process);
+            }
+        }
+
+        let current_pids: FxHashSet<Pid> = current.iter().map(|(pid, _)| *pid).collect();
+        delta.removed = previous
+            .keys()
+            .filter(|pid| !current_pids.contains(pid))
+            .copied()
+            .collect();
+        delta.removed.sort_unstable();
+        delta
+    }
+}
+
+/// The fields of `current` that aren't the same in `previous`.
+fn changed_fields(previous: &Fields, current: &Fields) -> Fields {
+    current
+        .iter()
+        .filter(|(name, value)| previous.get(*name) != Some(value))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+fn to_fields<T: Serialize>(value: &T) -> Fields {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields,
+        _ => unreachable!("serializes to an object"),
+    }
+}
+
+/// Turns each snapshot into the update to send for it.
+#[derive(Debug, Default)]
+pub struct DeltaEncoder {
+    seq: u64,
+    latest: Option<Data>,
+    /// The latest snapshot, apart from the process list.
+    fields: Fields,
+    /// The latest process list, if it's turned on.
+    processes: Option<FxHashMap<Pid, Fields>>,
+}
+
+impl DeltaEncoder {
+    pub fn encode(&mut self, data: &Data) -> DataUpdate {
+        let mut fields = to_fields(data);
+        fields.remove("list_of_processes");
+        let processes: Option<Vec<(Pid, Fields)>> = data.list_of_processes.as_ref().map(|list| {
+            list.iter()
+                .map(|process| (process.pid, to_fields(process)))
+                .collect()
+        });
+
+        self.seq += 1;
+        let update = if self.latest.is_none() {
+            DataUpdate::Full {
+                seq: self.seq,
+                data: Box::new(data.clone()),
+            }
+        } else {
+            let mut changed = changed_fields(&self.fields, &fields);
+            let processes = match (&self.processes, &processes) {
+                (Some(previous), Some(current)) => ProcessesDelta::between(previous, current),
+                (None, None) => ProcessesDelta::default(),
+                _ => {
+                    changed.insert(
+                        "list_of_processes".to_string(),
+                        serde_json::to_value(&data.list_of_processes).unwrap_or(Value::Null),
+                    );
+                    ProcessesDelta::default()
+                }
+            };
+            DataUpdate::Delta {
+                seq: self.seq,
+                changed,
+                processes,
+            }
+        };
+
+        self.latest = Some(data.clone());
+        self.fields = fields;
+        self.processes = processes.map(|list| list.into_iter().collect());
+        update
+    }
+
+    pub fn latest(&self) -> Option<&Data> {
+        self.latest.as_ref()
+    }
+
+    /// The latest snapshot in full, numbered the same as the update it was sent in. `None` if
+    /// nothing's been sent yet.
+    pub fn full(&self) -> Option<DataUpdate> {
+        self.latest.as_ref().map(|data| DataUpdate::Full {
+            seq: self.seq,
+            data: Box::new(data.clone()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_harvester::processes::ProcessHarvest;
+
+    #[test]
+    fn test_delta_encoding() {
+        let mut encoder = DeltaEncoder::default();
+        let init = ProcessHarvest::for_test(1, "init");
+        let make = ProcessHarvest {
+            cpu_usage_percent: 99.0,
+            ..ProcessHarvest::for_test(300, "make")
+        };
+        let mut data = Data {
+            hostname: Some("laptop".to_string()),
+            list_of_processes: Some(vec![
+                init.clone(),
+                ProcessHarvest {
+                    cpu_usage_percent: 12.5,
+                    ..ProcessHarvest::for_test(100, "firefox")
+                },
+                ProcessHarvest::for_test(200, "sleep"),
+            ]),
+            ..Data::default()
+        };
+        assert!(matches!(
+            encoder.encode(&data),
+            DataUpdate::Full { seq: 1, .. }
+        ));
+
+        data.os_version = Some("Linux 22.04 Ubuntu".to_string());
+        data.list_of_processes = Some(vec![
+            init,
+            ProcessHarvest {
+                cpu_usage_percent: 30.0,
+                ..ProcessHarvest::for_test(100, "firefox")
+            },
+            make.clone(),
+        ]);
+        let DataUpdate::Delta {
+            seq,
+            changed,
+            processes,
+        } = encoder.encode(&data)
+        else {
+            panic!("expected a delta");
+        };
+        assert_eq!(seq, 2);
+        assert!(changed.contains_key("os_version"));
+        assert!(!changed.contains_key("hostname"));
+        assert_eq!(processes.added, [to_fields(&make)]);
+        assert_eq!(processes.removed, [200]);
+        assert_eq!(
+            Value::Array(processes.changed.into_iter().map(Value::Object).collect()),
+            serde_json::json!([{ "pid": 100, "cpu_usage_percent": 30.0 }])
+        );
+
+        data.list_of_processes = None;
+        let DataUpdate::Delta {
+            changed, processes, ..
+        } = encoder.encode(&data)
+        else {
+            panic!("expected a delta");
+        };
+        assert_eq!(changed.get("list_of_processes"), Some(&Value::Null));
+        assert_eq!(processes, ProcessesDelta::default());
+
+        assert!(matches!(
+            encoder.full(),
+            Some(DataUpdate::Full { seq: 3, .. })
+        ));
+    }
+}
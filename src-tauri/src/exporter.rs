@@ -0,0 +1,109 @@
+//! An HTTP endpoint serving the latest snapshot as OpenMetrics, for Prometheus to scrape.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::config::ExporterConfig;
+use crate::metrics;
+use crate::utils::error::{self, ToeError};
+use crate::LatestData;
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+struct Exporter {
+    address: SocketAddr,
+    include_processes: bool,
+    server: Arc<Server>,
+    thread: JoinHandle<()>,
+}
+
+impl Exporter {
+    fn start(app_handle: AppHandle, config: &ExporterConfig) -> error::Result<Self> {
+        let server = Server::http(config.address).map_err(|err| {
+            ToeError::GenericError(format!(
+                "couldn't start the exporter on {}: {}",
+                config.address, err
+            ))
+        })?;
+        let server = Arc::new(server);
+
+        let include_processes = config.include_processes;
+        let thread = thread::Builder::new().name("exporter".to_string()).spawn({
+            let server = server.clone();
+            move || {
+                // Ends once the server is unblocked.
+                for request in server.incoming_requests() {
+                    respond(&app_handle, request, include_processes);
+                }
+            }
+        })?;
+
+        Ok(Exporter {
+            address: config.address,
+            include_processes,
+            server,
+            thread,
+        })
+    }
+
+    fn stop(self) {
+        self.server.unblock();
+        let _ = self.thread.join();
+    }
+}
+
+fn respond(app_handle: &AppHandle, request: Request, include_processes: bool) {
+    let response = match (request.method(), request.url()) {
+        (Method::Get, "/metrics") => {
+            let latest_data = app_handle.state::<LatestData>();
+            let samples = latest_data
+                .0
+                .lock()
+                .unwrap()
+                .latest()
+                .map(|data| metrics::samples(data, include_processes))
+                .unwrap_or_default();
+
+            let content_type = Header::from_bytes("Content-Type", CONTENT_TYPE).unwrap();
+            Response::from_string(metrics::render_openmetrics(&samples)).with_header(content_type)
+        }
+        (Method::Get, _) => Response::from_string("Not found; try /metrics").with_status_code(404),
+        _ => Response::from_string("Method not allowed").with_status_code(405),
+    };
+
+    // The scraper hanging up early isn't our problem.
+    let _ = request.respond(response);
+}
+
+/// The exporter, if it's enabled.
+#[derive(Default)]
+pub struct ExporterState(Mutex<Option<Exporter>>);
+
+impl ExporterState {
+    /// Starts, stops or restarts the exporter as needed to match `config`.
+    pub fn configure(&self, app_handle: &AppHandle, config: &ExporterConfig) -> error::Result<()> {
+        let mut exporter = self.0.lock().unwrap();
+        let unchanged = exporter.as_ref().map_or(!config.enabled, |exporter| {
+            config.enabled
+                && exporter.address == config.address
+                && exporter.include_processes == config.include_processes
+        });
+        if unchanged {
+            return Ok(());
+        }
+
+        if let Some(exporter) = exporter.take() {
+            exporter.stop();
+        }
+        if config.enabled {
+            *exporter = Some(Exporter::start(app_handle.clone(), config)?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+//! Keeps a history of every metric, so graphs survive the frontend re-rendering and can look further
+//! back than the last minute.
+//!
+//! Each series is kept in a few tiers of decreasing resolution, each a fixed-size ring buffer, so
+//! memory use stays flat however long the app runs. Optionally, the history is also written to a
+//! file so it survives restarts; see [`file`].
+
+mod file;
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::config::HistoryConfig;
+use crate::data_harvester::{unix_time_millis, Data};
+use crate::metrics::{self, MetricKind, Sample};
+use crate::utils::error;
+use crate::utils::logging::log_at;
+use file::HistoryFile;
+
+/// A resolution, and how many points at that resolution to keep.
+struct Tier {
+    /// In milliseconds.
+    resolution: u64,
+    capacity: usize,
+}
+
+impl Tier {
+    /// How far back this tier reaches, in milliseconds.
+    fn span(&self) -> u64 {
+        self.resolution * self.capacity as u64
+    }
+}
+
+/// From finest to coarsest: 1s for 10 minutes, 10s for 6 hours, 1min for a week.
+const TIERS: [Tier; 3] = [
+    Tier {
+        resolution: 1000,
+        capacity: 10 * 60,
+    },
+    Tier {
+        resolution: 10 * 1000,
+        capacity: 6 * 60 * 6,
+    },
+    Tier {
+        resolution: 60 * 1000,
+        capacity: 7 * 24 * 60,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Point {
+    /// The start of the interval this point covers, in milliseconds since the Unix epoch.
+    pub time: u64,
+    /// The mean of the samples taken in the interval.
+    pub value: f64,
+}
+
+/// The samples falling into the interval starting at `start`, not yet averaged into a point.
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    start: u64,
+    sum: f64,
+    count: u32,
+}
+
+impl Bucket {
+    fn point(&self) -> Point {
+        Point {
+            time: self.start,
+            value: self.sum / self.count as f64,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Ring {
+    points: VecDeque<Point>,
+    /// The interval currently being filled.
+    pending: Option<Bucket>,
+}
+
+impl Ring {
+    /// Adds a sample, dropping points from before `oldest`. Returns the point for the previous
+    /// interval if this sample is the first of a new one.
+    fn record(&mut self, tier: &Tier, time: u64, value: f64, oldest: u64) -> Option<Point> {
+        let start = time - time % tier.resolution;
+        let mut completed = None;
+        match &mut self.pending {
+            Some(bucket) if bucket.start == start => {
+                bucket.sum += value;
+                bucket.count += 1;
+                return None;
+            }
+            // The clock went backwards; averaging across the jump would be meaningless.
+            Some(bucket) if bucket.start > start => self.points.clear(),
+            Some(bucket) => {
+                if self.points.len() == tier.capacity {
+                    self.points.pop_front();
+                }
+                self.points.push_back(bucket.point());
+                completed = Some(bucket.point());
+            }
+            None => {}
+        }
+        self.pending = Some(Bucket {
+            start,
+            sum: value,
+            count: 1,
+        });
+
+        while self
+            .points
+            .front()
+            .map_or(false, |point| point.time < oldest)
+        {
+            self.points.pop_front();
+        }
+        completed
+    }
+
+    /// Puts points added out of order back in order, and trims the ring to size.
+    fn tidy(&mut self, tier: &Tier, oldest: u64) {
+        let mut points: Vec<Point> = self.points.drain(..).collect();
+        points.sort_by_key(|point| point.time);
+        // Of two points for the same interval, the one added later wins.
+        points.dedup_by(|later, earlier| {
+            let duplicate = later.time == earlier.time;
+            if duplicate {
+                *earlier = *later;
+            }
+            duplicate
+        });
+        self.points = points.into();
+        while self.points.len() > tier.capacity
+            || self
+                .points
+                .front()
+                .map_or(false, |point| point.time < oldest)
+        {
+            self.points.pop_front();
+        }
+    }
+
+    /// The points from `since` on, including the one still being filled.
+    fn points_since(&self, since: u64) -> impl Iterator<Item = Point> + '_ {
+        let first = self.points.partition_point(|point| point.time < since);
+        self.points
+            .range(first..)
+            .copied()
+            .chain(self.pending.as_ref().map(Bucket::point))
+            .filter(move |point| point.time >= since)
+    }
+}
+
+#[derive(Debug)]
+struct Series {
+    labels: Vec<(String, String)>,
+    tiers: [Ring; TIERS.len()],
+    last_recorded: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SeriesHistory {
+    pub labels: BTreeMap<String, String>,
+    pub points: Vec<Point>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryQuery {
+    /// The spacing of the points, in milliseconds.
+    pub resolution: u64,
+    /// One per label combination, e.g. one per CPU core.
+    pub series: Vec<SeriesHistory>,
+}
+
+/// A point that was just completed, and so is ready to be written out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletedPoint {
+    pub metric: String,
+    pub id: String,
+    pub tier: usize,
+    pub point: Point,
+}
+
+#[derive(Debug)]
+pub struct History {
+    /// Keyed by the sample name, then by [`Sample::id`].
+    series: HashMap<String, HashMap<String, Series>>,
+    /// Points older than this many milliseconds are dropped, even if there's room for them.
+    max_age: u64,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            series: HashMap::new(),
+            max_age: TIERS[TIERS.len() - 1].span(),
+        }
+    }
+}
+
+impl History {
+    pub fn record(&mut self, samples: &[Sample], time: u64) -> Vec<CompletedPoint> {
+        let oldest = time.saturating_sub(self.max_age);
+        let mut completed = Vec::new();
+        for sample in samples {
+            // These don't change over time, and aren't really numbers.
+            if sample.family.kind == MetricKind::Info {
+                continue;
+            }
+
+            let metric = sample.family.sample_name();
+            let id = sample.id();
+            let series = self
+                .series
+                .entry(metric.clone())
+                .or_default()
+                .entry(id.clone())
+                .or_insert_with(|| Series {
+                    labels: sample
+                        .labels
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.clone()))
+                        .collect(),
+                    tiers: Default::default(),
+                    last_recorded: time,
+                });
+            for (tier, (ring, tier_spec)) in series.tiers.iter_mut().zip(&TIERS).enumerate() {
+                if let Some(point) = ring.record(tier_spec, time, sample.value, oldest) {
+                    completed.push(CompletedPoint {
+                        metric: metric.clone(),
+                        id: id.clone(),
+                        tier,
+                        point,
+                    });
+                }
+            }
+            series.last_recorded = time;
+        }
+
+        // Forget about things that went away for good, like unplugged disks.
+        for family in self.series.values_mut() {
+            family.retain(|_, series| series.last_recorded >= oldest);
+        }
+        self.series.retain(|_, family| !family.is_empty());
+
+        completed
+    }
+
+    /// Adds a point read back from a file. [`History::tidy`] needs to be called once they're all in.
+    fn restore(&mut self, labels: &[(String, String)], point: CompletedPoint) {
+        let series = self
+            .series
+            .entry(point.metric)
+            .or_default()
+            .entry(point.id)
+            .or_insert_with(|| Series {
+                labels: labels.to_vec(),
+                tiers: Default::default(),
+                last_recorded: point.point.time,
+            });
+        series.tiers[point.tier].points.push_back(point.point);
+        series.last_recorded = series.last_recorded.max(point.point.time);
+    }
+
+    fn tidy(&mut self, now: u64) {
+        let oldest = now.saturating_sub(self.max_age);
+        for family in self.series.values_mut() {
+            for series in family.values_mut() {
+                for (ring, tier) in series.tiers.iter_mut().zip(&TIERS) {
+                    ring.tidy(tier, oldest);
+                }
+            }
+            family.retain(|_, series| series.last_recorded >= oldest);
+        }
+        self.series.retain(|_, family| !family.is_empty());
+    }
+
+    /// Returns the last `range` milliseconds of the metric named `metric`, with points at least
+    /// `resolution` milliseconds apart, or as close to that as the tiers allow.
+    ///
+    /// Uses the coarsest tier that reaches back far enough and is still fine enough. If none
+    /// reaches back far enough, the coarsest is used and the result is cut short.
+    pub fn query(&self, metric: &str, range: u64, resolution: u64, now: u64) -> HistoryQuery {
+        let reaching = || (0..TIERS.len()).filter(|&i| TIERS[i].span() >= range);
+        let tier_index = reaching()
+            .rfind(|&i| TIERS[i].resolution <= resolution)
+            .or_else(|| reaching().next())
+            .unwrap_or(TIERS.len() - 1);
+        // Only ever coarser than the tier, since finer points don't exist.
+        let resolution = resolution.max(TIERS[tier_index].resolution);
+        let since = now.saturating_sub(range);
+
+        let mut series: Vec<SeriesHistory> = self
+            .series
+            .get(metric)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .map(|series| SeriesHistory {
+                labels: series.labels.iter().cloned().collect(),
+                points: downsample(series.tiers[tier_index].points_since(since), resolution),
+            })
+            .filter(|series| !series.points.is_empty())
+            .collect();
+        series.sort_by(|a, b| a.labels.cmp(&b.labels));
+
+        HistoryQuery { resolution, series }
+    }
+}
+
+/// Averages points into intervals of `resolution` milliseconds.
+fn downsample(points: impl Iterator<Item = Point>, resolution: u64) -> Vec<Point> {
+    let mut downsampled = Vec::new();
+    let mut bucket: Option<Bucket> = None;
+    for point in points {
+        let start = point.time - point.time % resolution;
+        match &mut bucket {
+            Some(bucket) if bucket.start == start => {
+                bucket.sum += point.value;
+                bucket.count += 1;
+                continue;
+            }
+            Some(bucket) => downsampled.push(bucket.point()),
+            None => {}
+        }
+        bucket = Some(Bucket {
+            start,
+            sum: point.value,
+            count: 1,
+        });
+    }
+    downsampled.extend(bucket.as_ref().map(Bucket::point));
+    downsampled
+}
+
+#[derive(Default)]
+pub struct HistoryState {
+    history: Mutex<History>,
+    /// Where the history is written to, if it's kept across restarts.
+    file: Mutex<Option<HistoryFile>>,
+}
+
+impl HistoryState {
+    /// Adds a snapshot to the history. Per-process metrics are left out, as there are too many.
+    pub fn record(&self, data: &Data) {
+        let samples = metrics::samples(data, false);
+        let mut history = self.history.lock().unwrap();
+        let completed = history.record(&samples, unix_time_millis());
+
+        let mut file = self.file.lock().unwrap();
+        if let Some(history_file) = file.as_mut() {
+            if let Err(err) = history_file.append(&history, &completed) {
+                // Rather than failing again every second. The history is still kept in memory.
+                log_at!(
+                    error,
+                    "Couldn't write the history, so it won't be saved: {}",
+                    err
+                );
+                *file = None;
+            }
+        }
+    }
+
+    /// Applies the retention settings, and starts or stops saving the history to a file. Starting
+    /// loads what was saved before.
+    pub fn configure(&self, config: &HistoryConfig) -> error::Result<()> {
+        let mut history = self.history.lock().unwrap();
+        history.max_age = config.max_age.as_millis() as u64;
+        history.tidy(unix_time_millis());
+
+        let mut file = self.file.lock().unwrap();
+        match (config.persist, file.as_mut()) {
+            (false, _) => *file = None,
+            (true, Some(history_file)) => {
+                history_file.set_max_size(config.max_size_mib * 1024 * 1024)
+            }
+            (true, None) => {
+                let path = file::default_path().ok_or_else(|| {
+                    error::ToeError::GenericError(
+                        "couldn't find a directory to save the history in".to_string(),
+                    )
+                })?;
+                let max_size = config.max_size_mib * 1024 * 1024;
+                *file = Some(HistoryFile::open(&path, &mut history, max_size)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the last `range` milliseconds of a metric, e.g. `toerings_cpu_usage_ratio`, with points
+/// `resolution` milliseconds apart. Without a resolution, the finest one available is used.
+#[tauri::command]
+pub fn get_history(
+    history: State<HistoryState>,
+    metric: String,
+    range: u64,
+    resolution: Option<u64>,
+) -> HistoryQuery {
+    history.history.lock().unwrap().query(
+        &metric,
+        range,
+        resolution.unwrap_or(0),
+        unix_time_millis(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{CPU_USAGE, MEMORY_USAGE};
+
+    fn sample(value: f64) -> Sample {
+        Sample {
+            family: &CPU_USAGE,
+            labels: vec![("cpu", "0".to_string())],
+            value,
+        }
+    }
+
+    #[test]
+    fn test_history_tiers() {
+        let mut history = History::default();
+        let start = 1_000_000 * 60 * 1000;
+        // 20 minutes at two samples per second, alternating between 0 and 1.
+        for i in 0..20 * 60 * 2 {
+            history.record(&[sample((i % 2) as f64)], start + i * 500);
+        }
+        let now = start + 20 * 60 * 1000;
+
+        // The finest tier only has the last 10 minutes.
+        let recent = history.query("toerings_cpu_usage_ratio", 5 * 60 * 1000, 0, now);
+        assert_eq!(recent.resolution, 1000);
+        assert_eq!(recent.series.len(), 1);
+        assert_eq!(recent.series[0].labels["cpu"], "0");
+        assert_eq!(recent.series[0].points.len(), 5 * 60);
+        assert!(recent.series[0]
+            .points
+            .iter()
+            .all(|point| point.value == 0.5));
+
+        let all = history.query("toerings_cpu_usage_ratio", 60 * 60 * 1000, 0, now);
+        assert_eq!(all.resolution, 10 * 1000);
+        assert_eq!(all.series[0].points.len(), 20 * 6);
+        assert_eq!(all.series[0].points[0].time, start);
+
+        let coarse = history.query(
+            "toerings_cpu_usage_ratio",
+            60 * 60 * 1000,
+            5 * 60 * 1000,
+            now,
+        );
+        assert_eq!(coarse.resolution, 5 * 60 * 1000);
+        assert_eq!(coarse.series[0].points.len(), 4);
+
+        assert!(history
+            .query(MEMORY_USAGE.name, 1000, 0, now)
+            .series
+            .is_empty());
+    }
+
+    #[test]
+    fn test_history_ring_is_bounded() {
+        let mut ring = Ring::default();
+        let tier = &TIERS[0];
+        for i in 0..tier.capacity as u64 * 3 {
+            ring.record(tier, i * tier.resolution, i as f64, 0);
+        }
+        assert_eq!(ring.points.len(), tier.capacity);
+        assert_eq!(ring.points_since(0).count(), tier.capacity + 1);
+    }
+}
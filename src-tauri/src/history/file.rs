@@ -0,0 +1,353 @@
+//! Saves the history to a file, so the graphs pick up where they left off after a restart.
+//!
+//! The file is append-only: points are appended as they're completed, and once the file grows past
+//! its size limit it's rewritten with only what's still in the history. A file cut short or garbled
+//! by a crash is read up to the damage, and the rest is dropped.
+//!
+//! After a header, the file is a sequence of records, each of which is
+//!
+//! | Bytes | Contents                                 |
+//! |-------|------------------------------------------|
+//! | 4     | The length of the payload, little-endian |
+//! | 4     | The CRC-32 of the payload, little-endian |
+//! | n     | The payload                              |
+//!
+//! The payload is either `0` followed by a series definition as JSON, or `1` followed by any number
+//! of points. Each point is its series' index (u32), tier (u8), time (u64) and value (f64), all
+//! little-endian.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{CompletedPoint, History, Point, TIERS};
+use crate::data_harvester::unix_time_millis;
+use crate::utils::logging::log_at;
+
+const MAGIC: &[u8] = b"toerings history 1\n";
+const SERIES: u8 = 0;
+const POINTS: u8 = 1;
+const POINT_LEN: usize = 4 + 1 + 8 + 8;
+/// A length bigger than this means the record is garbage.
+const MAX_RECORD_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SeriesRecord {
+    index: u32,
+    metric: String,
+    id: String,
+    labels: Vec<(String, String)>,
+}
+
+/// `~/.local/share/toerings/history.bin` on Linux.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("toerings").join("history.bin"))
+}
+
+pub struct HistoryFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    /// The index each series was given in this file, by id.
+    indices: HashMap<String, u32>,
+    len: u64,
+    /// In bytes.
+    max_size: u64,
+}
+
+impl HistoryFile {
+    /// Opens the file at `path`, creating it if there isn't one, and adds what's in it to `history`.
+    pub fn open(path: &Path, history: &mut History, max_size: u64) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        match fs::read(path) {
+            Ok(contents) => {
+                if !load(&contents, history) {
+                    // Keep it around in case it's something that matters.
+                    let aside = path.with_extension("bin.unreadable");
+                    log_at!(
+                        warn,
+                        "{} isn't a history file, so it's been moved to {}",
+                        path.display(),
+                        aside.display()
+                    );
+                    fs::rename(path, aside)?;
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        history.tidy(unix_time_millis());
+
+        // This also gets rid of anything damaged at the end.
+        HistoryFile::create(path, history, max_size)
+    }
+
+    /// Writes out `history` to a new file, which then replaces the one at `path`.
+    fn create(path: &Path, history: &History, max_size: u64) -> io::Result<Self> {
+        let tmp_path = path.with_extension("bin.tmp");
+        let mut file = HistoryFile {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(File::create(&tmp_path)?),
+            indices: HashMap::new(),
+            len: 0,
+            max_size,
+        };
+        file.writer.write_all(MAGIC)?;
+        file.len += MAGIC.len() as u64;
+
+        // Leave room to append to before the file needs rewriting again.
+        let cutoff = cutoff(history, max_size / 2);
+        let mut batch = Vec::new();
+        for (metric, family) in &history.series {
+            for (id, series) in family {
+                let points: Vec<_> = series
+                    .tiers
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(tier, ring)| ring.points.iter().map(move |point| (tier, point)))
+                    .filter(|(_, point)| point.time >= cutoff)
+                    .collect();
+                if points.is_empty() {
+                    continue;
+                }
+
+                let index = file.define(metric, id, &series.labels)?;
+                batch.clear();
+                for (tier, point) in points {
+                    encode_point(&mut batch, index, tier, point);
+                }
+                file.write_record(POINTS, &batch)?;
+            }
+        }
+
+        file.writer.flush()?;
+        file.writer.get_ref().sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(file)
+    }
+
+    pub fn set_max_size(&mut self, max_size: u64) {
+        self.max_size = max_size;
+    }
+
+    /// Appends newly completed points, rewriting the file if that takes it over its size limit.
+    pub fn append(&mut self, history: &History, completed: &[CompletedPoint]) -> io::Result<()> {
+        if completed.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = Vec::with_capacity(completed.len() * POINT_LEN);
+        for completed_point in completed {
+            let index = match self.indices.get(&completed_point.id) {
+                Some(&index) => index,
+                None => {
+                    let labels = history
+                        .series
+                        .get(&completed_point.metric)
+                        .and_then(|family| family.get(&completed_point.id))
+                        .map(|series| series.labels.as_slice())
+                        .unwrap_or_default();
+                    self.define(&completed_point.metric, &completed_point.id, labels)?
+                }
+            };
+            encode_point(
+                &mut batch,
+                index,
+                completed_point.tier,
+                &completed_point.point,
+            );
+        }
+        self.write_record(POINTS, &batch)?;
+        self.writer.flush()?;
+
+        if self.len > self.max_size {
+            *self = HistoryFile::create(&self.path, history, self.max_size)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a series definition, returning the index it's referred to by from then on.
+    fn define(&mut self, metric: &str, id: &str, labels: &[(String, String)]) -> io::Result<u32> {
+        let record = SeriesRecord {
+            index: self.indices.len() as u32,
+            metric: metric.to_string(),
+            id: id.to_string(),
+            labels: labels.to_vec(),
+        };
+        self.write_record(SERIES, &serde_json::to_vec(&record)?)?;
+        self.indices.insert(record.id, record.index);
+        Ok(record.index)
+    }
+
+    fn write_record(&mut self, kind: u8, body: &[u8]) -> io::Result<()> {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&[kind]);
+        hasher.update(body);
+
+        let len = 1 + body.len();
+        self.writer.write_all(&(len as u32).to_le_bytes())?;
+        self.writer.write_all(&hasher.finalize().to_le_bytes())?;
+        self.writer.write_all(&[kind])?;
+        self.writer.write_all(body)?;
+        self.len += 8 + len as u64;
+        Ok(())
+    }
+}
+
+/// The time before which points have to be left out for the file to fit in roughly `budget`
+/// bytes, dropping the oldest first.
+fn cutoff(history: &History, budget: u64) -> u64 {
+    let mut times: Vec<u64> = history
+        .series
+        .values()
+        .flat_map(|family| family.values())
+        .flat_map(|series| series.tiers.iter())
+        .flat_map(|ring| ring.points.iter().map(|point| point.time))
+        .collect();
+    let max_points = budget as usize / POINT_LEN;
+    if times.len() <= max_points {
+        return 0;
+    }
+
+    times.sort_unstable_by(|a, b| b.cmp(a));
+    times[max_points] + 1
+}
+
+fn encode_point(batch: &mut Vec<u8>, index: u32, tier: usize, point: &Point) {
+    batch.extend_from_slice(&index.to_le_bytes());
+    batch.push(tier as u8);
+    batch.extend_from_slice(&point.time.to_le_bytes());
+    batch.extend_from_slice(&point.value.to_le_bytes());
+}
+
+/// Adds the points in `contents` to `history`, as far as they can be read. Returns whether it's a
+/// history file at all.
+fn load(contents: &[u8], history: &mut History) -> bool {
+    if contents.is_empty() {
+        return true;
+    }
+    let mut rest = match contents.strip_prefix(MAGIC) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut series: HashMap<u32, SeriesRecord> = HashMap::new();
+    while let Some((kind, body, next)) = read_record(rest) {
+        rest = next;
+        match kind {
+            SERIES => {
+                if let Ok(record) = serde_json::from_slice::<SeriesRecord>(body) {
+                    series.insert(record.index, record);
+                }
+            }
+            POINTS => {
+                for encoded in body.chunks_exact(POINT_LEN) {
+                    let index = u32::from_le_bytes(encoded[0..4].try_into().unwrap());
+                    let tier = encoded[4] as usize;
+                    let time = u64::from_le_bytes(encoded[5..13].try_into().unwrap());
+                    let value = f64::from_le_bytes(encoded[13..21].try_into().unwrap());
+                    if let (Some(record), true) = (series.get(&index), tier < TIERS.len()) {
+                        history.restore(
+                            &record.labels,
+                            CompletedPoint {
+                                metric: record.metric.clone(),
+                                id: record.id.clone(),
+                                tier,
+                                point: Point { time, value },
+                            },
+                        );
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !rest.is_empty() {
+        log_at!(
+            warn,
+            "The history file is damaged {} bytes in, probably from a crash; the rest was dropped",
+            contents.len() - rest.len()
+        );
+    }
+    true
+}
+
+/// Splits off the first record, if there's an intact one, returning its kind, body and what comes
+/// after it.
+fn read_record(bytes: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let len = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+    if len == 0 || len > MAX_RECORD_LEN {
+        return None;
+    }
+    let payload = bytes.get(8..8 + len)?;
+    if crc32fast::hash(payload) != crc {
+        return None;
+    }
+    Some((payload[0], &payload[1..], &bytes[8 + len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{Sample, CPU_USAGE};
+
+    #[test]
+    fn test_history_file_recovers_from_damage() {
+        let dir = std::env::temp_dir().join(format!("toerings-history-{}", std::process::id()));
+        let path = dir.join("history.bin");
+        let _ = fs::remove_dir_all(&dir);
+
+        let now = unix_time_millis();
+        let mut history = History::default();
+        let mut file = HistoryFile::open(&path, &mut history, 1024 * 1024).unwrap();
+        for i in 0..30 {
+            let sample = Sample {
+                family: &CPU_USAGE,
+                labels: vec![("cpu", "0".to_string())],
+                value: i as f64,
+            };
+            let time = now - 30_000 + i * 1000;
+            let completed = history.record(&[sample], time);
+            file.append(&history, &completed).unwrap();
+        }
+        drop(file);
+
+        // As if the app crashed halfway through writing a record.
+        let mut contents = fs::read(&path).unwrap();
+        contents.extend_from_slice(&[40, 0, 0, 0, 1, 2, 3, 4, 1]);
+        fs::write(&path, contents).unwrap();
+
+        let mut reloaded = History::default();
+        HistoryFile::open(&path, &mut reloaded, 1024 * 1024).unwrap();
+        let query = |history: &History| history.query("toerings_cpu_usage_ratio", 60_000, 0, now);
+        let mut expected = query(&history);
+        // The interval that was still being filled never made it to the file.
+        expected.series[0].points.pop();
+        assert_eq!(query(&reloaded), expected);
+        assert_eq!(expected.series[0].points.len(), 29);
+
+        // The damaged end is gone after a rewrite, so the file is readable again.
+        let contents = fs::read(&path).unwrap();
+        let mut rest = contents.strip_prefix(MAGIC).unwrap();
+        while let Some((_, _, next)) = read_record(rest) {
+            rest = next;
+        }
+        assert!(rest.is_empty());
+
+        // Something else entirely is moved aside.
+        fs::write(&path, "not a history file").unwrap();
+        HistoryFile::open(&path, &mut History::default(), 1024 * 1024).unwrap();
+        assert!(dir.join("history.bin.unreadable").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,14 +3,35 @@
     windows_subsystem = "windows"
 )]
 
+mod alerts;
+mod cli;
+mod collection;
+mod config;
 mod data_harvester;
+mod delta;
+mod exporter;
+mod history;
+mod metrics;
+mod process_control;
+mod process_details;
+mod process_events;
+mod process_tree;
+mod query;
+mod recording;
 mod utils;
 
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-use crate::utils::error;
+use crate::utils::logging::log_at;
+use alerts::AlertsState;
 use data_harvester::{Data, DataCollector};
-use tauri::{AboutMetadata, CustomMenuItem, Menu, MenuItem, Submenu};
+use delta::{DataUpdate, DeltaEncoder};
+use exporter::ExporterState;
+use history::HistoryState;
+use process_events::ProcessEventsState;
+use process_tree::ProcessTreeState;
+use recording::{RecordingState, ReplayState};
+use tauri::{AboutMetadata, AppHandle, CustomMenuItem, Manager, Menu, MenuItem, Submenu};
 
 #[cfg(target_family = "windows")]
 pub type Pid = usize;
@@ -18,15 +39,74 @@ pub type Pid = usize;
 #[cfg(target_family = "unix")]
 pub type Pid = libc::pid_t;
 
+/// The most recent snapshot published by the collection thread, and what the frontend has been
+/// sent of it.
+#[derive(Default)]
+struct LatestData(Mutex<DeltaEncoder>);
+
+/// Returns the most recent snapshot in full, so a freshly (re)loaded frontend doesn't have to wait
+/// for the next `dataCollected` event to draw something, and one that missed an update can catch
+/// up.
+#[tauri::command]
+fn get_latest_data(latest_data: tauri::State<LatestData>) -> Option<DataUpdate> {
+    latest_data.0.lock().unwrap().full()
+}
+
+/// Starts or stops listing the threads of the process `pid`, in `threads` from the next refresh of
+/// the process list.
 #[tauri::command]
-fn collect_data(data_state: tauri::State<Mutex<DataCollector>>) -> Data {
-    futures::executor::block_on(data_state.lock().unwrap().update_data());
-    data_state.lock().unwrap().data.clone()
+fn set_thread_collection(
+    collector: tauri::State<Arc<Mutex<DataCollector>>>,
+    pid: Pid,
+    enabled: bool,
+) {
+    collector
+        .lock()
+        .unwrap()
+        .set_thread_collection(pid, enabled);
+}
+
+/// Hands a snapshot to the frontend, and to anything else showing the latest one.
+fn publish(app_handle: &AppHandle, data: &Data) {
+    // Encoding and emitting under the lock keeps the updates in order, as the replay thread can
+    // publish too.
+    let latest_data = app_handle.state::<LatestData>();
+    let mut encoder = latest_data.0.lock().unwrap();
+    let _ = app_handle.emit_all("dataCollected", encoder.encode(data));
+}
+
+/// Starts logging at the level from the config file, which [`config::Config::apply_to_app`] keeps
+/// up to date from then on.
+fn init_logging(config_state: &config::ConfigState) {
+    #[cfg(feature = "fern")]
+    {
+        let level = config_state
+            .config()
+            .map(|config| config.logging.level)
+            .unwrap_or_default();
+        let initialized = match utils::logging::default_path() {
+            Some(path) => utils::logging::init_logger(level, &path).map_err(|err| err.to_string()),
+            None => Err("couldn't find a directory for the log file".to_string()),
+        };
+        if let Err(err) = initialized {
+            // Better there than nowhere.
+            let _ = utils::logging::init_stderr_logger(level);
+            log_at!(
+                error,
+                "Couldn't open the log file, so logging to stderr: {}",
+                err
+            );
+        }
+    }
+    #[cfg(not(feature = "fern"))]
+    let _ = config_state;
 }
 
 fn main() {
-    let mut data_state = DataCollector::new();
-    data_state.init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_requested(&args) {
+        std::process::exit(cli::run(&args));
+    }
 
     let preferences = CustomMenuItem::new("preferences", "Open Preferences").accelerator("cmd+,");
     let submenu = Submenu::new(
@@ -51,14 +131,80 @@ fn main() {
     );
     let menu = Menu::new().add_submenu(submenu);
 
+    let config_state = config::ConfigState::load();
+    init_logging(&config_state);
+
+    let collector = Arc::new(Mutex::new(DataCollector::new()));
+
     tauri::Builder::default()
-        .manage(Mutex::new(data_state))
+        .manage(LatestData::default())
+        .manage(config_state)
+        .manage(collector.clone())
+        .manage(ExporterState::default())
+        .manage(AlertsState::default())
+        .manage(HistoryState::default())
+        .manage(RecordingState::default())
+        .manage(ReplayState::default())
+        .manage(ProcessTreeState::default())
+        .manage(ProcessEventsState::default())
+        .setup(|app| {
+            let app_handle = app.handle();
+            let applied = app
+                .state::<config::ConfigState>()
+                .config()
+                .and_then(|config| config.apply_to_app(&app_handle).map_err(Into::into));
+            if let Err(err) = applied {
+                // If it's the config file that's broken, the frontend gets this error too, through
+                // `get_config`.
+                log_at!(error, "Couldn't apply the config: {}", err);
+            }
+
+            collection::spawn(collector, move |data| {
+                app_handle.state::<HistoryState>().record(data);
+                app_handle.state::<RecordingState>().record(data);
+                app_handle.state::<AlertsState>().check(&app_handle, data);
+                app_handle
+                    .state::<ProcessEventsState>()
+                    .check(&app_handle, data);
+                // A replay is showing recorded snapshots instead.
+                if !app_handle.state::<ReplayState>().is_active() {
+                    publish(&app_handle, data);
+                }
+            });
+            config::watch(app.handle());
+            Ok(())
+        })
         .menu(menu)
         .on_menu_event(|event| match event.menu_item_id() {
             "preferences" => event.window().emit("openPreferences", ()).unwrap(),
             _ => {}
         })
-        .invoke_handler(tauri::generate_handler![collect_data])
+        .invoke_handler(tauri::generate_handler![
+            get_latest_data,
+            set_thread_collection,
+            config::get_config,
+            config::set_config,
+            history::get_history,
+            alerts::get_alert_history,
+            recording::start_recording,
+            recording::stop_recording,
+            recording::get_recording_path,
+            recording::start_replay,
+            recording::control_replay,
+            recording::stop_replay,
+            recording::get_replay_status,
+            utils::logging::get_log_tail,
+            query::query_processes,
+            process_tree::get_process_tree,
+            process_tree::get_process_groups,
+            process_tree::set_process_collapsed,
+            process_control::send_signal,
+            process_control::set_nice,
+            process_control::set_cpu_affinity,
+            process_control::set_io_priority,
+            process_details::get_process_details,
+            process_events::get_process_events
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,443 @@
+//! Flattens a [`Data`] snapshot into labelled numbers, in the style of Prometheus metrics.
+//!
+//! Values use base units: ratios rather than percentages, bytes rather than bits or KiB, seconds.
+
+use std::fmt::Write;
+
+use crate::data_harvester::{cpu::CpuDataType, memory::MemHarvest, Data, Subsystem};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Gauge,
+    Counter,
+    /// A constant `1`, with the information in the labels.
+    Info,
+}
+
+impl MetricKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Gauge => "gauge",
+            MetricKind::Counter => "counter",
+            MetricKind::Info => "info",
+        }
+    }
+}
+
+/// A kind of measurement, of which a snapshot can have any number of samples with different labels.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MetricFamily {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub unit: Option<&'static str>,
+    pub help: &'static str,
+}
+
+impl MetricFamily {
+    /// The name of this family's samples. Counters and infos get a suffix.
+    pub fn sample_name(&self) -> String {
+        match self.kind {
+            MetricKind::Gauge => self.name.to_string(),
+            MetricKind::Counter => format!("{}_total", self.name),
+            MetricKind::Info => format!("{}_info", self.name),
+        }
+    }
+}
+
+macro_rules! families {
+    ($($ident:ident: $name:literal, $kind:ident, $unit:expr, $help:literal;)*) => {
+        $(
+            pub static $ident: MetricFamily = MetricFamily {
+                name: $name,
+                kind: MetricKind::$kind,
+                unit: $unit,
+                help: $help,
+            };
+        )*
+
+        /// Every family, in the order they're rendered.
+        pub static FAMILIES: &[&MetricFamily] = &[$(&$ident),*];
+    };
+}
+
+families! {
+    CPU_USAGE: "toerings_cpu_usage_ratio", Gauge, Some("ratio"), "Usage of each CPU core.";
+    CPU_AVERAGE_USAGE: "toerings_cpu_average_usage_ratio", Gauge, Some("ratio"), "Usage averaged over all CPU cores.";
+    LOAD_AVERAGE: "toerings_load_average", Gauge, None, "Load average over the last 1, 5 and 15 minutes.";
+    MEMORY_USED: "toerings_memory_used_bytes", Gauge, Some("bytes"), "Used RAM, swap or ZFS ARC.";
+    MEMORY_TOTAL: "toerings_memory_total_bytes", Gauge, Some("bytes"), "Total RAM, swap or ZFS ARC.";
+    MEMORY_USAGE: "toerings_memory_usage_ratio", Gauge, Some("ratio"), "Used over total RAM, swap or ZFS ARC.";
+    GPU_MEMORY_USED: "toerings_gpu_memory_used_bytes", Gauge, Some("bytes"), "Used memory of each GPU.";
+    GPU_MEMORY_TOTAL: "toerings_gpu_memory_total_bytes", Gauge, Some("bytes"), "Total memory of each GPU.";
+    DISK_FREE: "toerings_disk_free_bytes", Gauge, Some("bytes"), "Free space on each mounted disk.";
+    DISK_USED: "toerings_disk_used_bytes", Gauge, Some("bytes"), "Used space on each mounted disk.";
+    DISK_TOTAL: "toerings_disk_total_bytes", Gauge, Some("bytes"), "Size of each mounted disk.";
+    DISK_READ: "toerings_disk_read_bytes", Counter, Some("bytes"), "Bytes read from each disk.";
+    DISK_WRITTEN: "toerings_disk_written_bytes", Counter, Some("bytes"), "Bytes written to each disk.";
+    NETWORK_RECEIVE: "toerings_network_receive_bytes", Counter, Some("bytes"), "Bytes received over all network interfaces.";
+    NETWORK_TRANSMIT: "toerings_network_transmit_bytes", Counter, Some("bytes"), "Bytes sent over all network interfaces.";
+    NETWORK_RECEIVE_RATE: "toerings_network_receive_bytes_per_second", Gauge, Some("bytes_per_second"), "Bytes received per second over all network interfaces.";
+    NETWORK_TRANSMIT_RATE: "toerings_network_transmit_bytes_per_second", Gauge, Some("bytes_per_second"), "Bytes sent per second over all network interfaces.";
+    TEMPERATURE: "toerings_temperature_celsius", Gauge, Some("celsius"), "Reading of each temperature sensor.";
+    BATTERY_CHARGE: "toerings_battery_charge_ratio", Gauge, Some("ratio"), "Charge of each battery.";
+    BATTERY_HEALTH: "toerings_battery_health_ratio", Gauge, Some("ratio"), "Capacity of each battery relative to its design capacity.";
+    BATTERY_POWER: "toerings_battery_power_watts", Gauge, Some("watts"), "Rate at which each battery is charging or discharging.";
+    BATTERY_DISCHARGING: "toerings_battery_discharging", Gauge, None, "Whether each battery is discharging.";
+    BATTERY_TIME_TO_FULL: "toerings_battery_time_to_full_seconds", Gauge, Some("seconds"), "Estimated time until each charging battery is full.";
+    BATTERY_TIME_TO_EMPTY: "toerings_battery_time_to_empty_seconds", Gauge, Some("seconds"), "Estimated time until each discharging battery is empty.";
+    PROCESSES: "toerings_processes", Gauge, None, "Number of processes.";
+    PROCESSES_READ_RATE: "toerings_processes_read_bytes_per_second", Gauge, Some("bytes_per_second"), "Bytes read per second by all processes.";
+    PROCESSES_WRITE_RATE: "toerings_processes_written_bytes_per_second", Gauge, Some("bytes_per_second"), "Bytes written per second by all processes.";
+    PROCESS_CPU_USAGE: "toerings_process_cpu_usage_ratio", Gauge, Some("ratio"), "CPU usage of each process.";
+    PROCESS_MEMORY: "toerings_process_memory_bytes", Gauge, Some("bytes"), "Memory used by each process.";
+    PROCESS_READ: "toerings_process_read_bytes", Counter, Some("bytes"), "Bytes read by each process.";
+    PROCESS_WRITTEN: "toerings_process_written_bytes", Counter, Some("bytes"), "Bytes written by each process.";
+    UPTIME: "toerings_uptime_seconds", Gauge, Some("seconds"), "Time since the machine booted.";
+    HOST: "toerings_host", Info, None, "Hostname, kernel and OS of the machine.";
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub family: &'static MetricFamily,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl Sample {
+    /// Identifies the series this sample belongs to, e.g. `toerings_cpu_usage_ratio{cpu="0"}`.
+    pub fn id(&self) -> String {
+        let mut id = self.family.sample_name();
+        write_labels(&mut id, &self.labels);
+        id
+    }
+}
+
+#[derive(Default)]
+struct Samples(Vec<Sample>);
+
+impl Samples {
+    fn push(&mut self, family: &'static MetricFamily, labels: &[(&'static str, &str)], value: f64) {
+        self.0.push(Sample {
+            family,
+            labels: labels
+                .iter()
+                .map(|(name, value)| (*name, value.to_string()))
+                .collect(),
+            value,
+        });
+    }
+
+    fn push_memory(&mut self, memory: &MemHarvest, name: &str) {
+        let labels = [("memory", name)];
+        let used = (memory.mem_used_in_kib * 1024) as f64;
+        let total = (memory.mem_total_in_kib * 1024) as f64;
+        self.push(&MEMORY_USED, &labels, used);
+        self.push(&MEMORY_TOTAL, &labels, total);
+        if let Some(use_percent) = memory.use_percent {
+            self.push(&MEMORY_USAGE, &labels, use_percent / 100.0);
+        }
+    }
+}
+
+/// Turns `data` into samples. Per-process samples are left out unless `include_processes` is set,
+/// as there are a lot of them and they come and go.
+pub fn samples(data: &Data, include_processes: bool) -> Vec<Sample> {
+    let mut samples = Samples::default();
+
+    if let Some(cpu) = &data.cpu {
+        let mut average = None;
+        let mut cores = Vec::new();
+        for cpu_data in cpu {
+            match cpu_data.data_type {
+                CpuDataType::Avg => average = Some(cpu_data.cpu_usage / 100.0),
+                CpuDataType::Cpu(index) => {
+                    let usage = cpu_data.cpu_usage / 100.0;
+                    samples.push(&CPU_USAGE, &[("cpu", &index.to_string())], usage);
+                    cores.push(usage);
+                }
+            }
+        }
+
+        // The average is only collected if it's shown, but it's too useful to leave out.
+        let average = average.or_else(|| {
+            (!cores.is_empty()).then(|| cores.iter().sum::<f64>() / cores.len() as f64)
+        });
+        if let Some(average) = average {
+            samples.push(&CPU_AVERAGE_USAGE, &[], average);
+        }
+    }
+    if let Some(load_avg) = &data.load_avg {
+        for (period, load) in ["1m", "5m", "15m"].iter().zip(load_avg) {
+            samples.push(&LOAD_AVERAGE, &[("period", period)], *load as f64);
+        }
+    }
+
+    if let Some(memory) = &data.memory {
+        samples.push_memory(memory, "ram");
+    }
+    if let Some(swap) = &data.swap {
+        samples.push_memory(swap, "swap");
+    }
+    #[cfg(feature = "zfs")]
+    if let Some(arc) = &data.arc {
+        samples.push_memory(arc, "arc");
+    }
+    #[cfg(feature = "gpu")]
+    if let Some(gpus) = &data.gpu {
+        for (name, memory) in gpus {
+            let labels = [("gpu", name.as_str())];
+            let used = (memory.mem_used_in_kib * 1024) as f64;
+            let total = (memory.mem_total_in_kib * 1024) as f64;
+            samples.push(&GPU_MEMORY_USED, &labels, used);
+            samples.push(&GPU_MEMORY_TOTAL, &labels, total);
+        }
+    }
+
+    if let Some(disks) = &data.disks {
+        for disk in disks {
+            let labels = [
+                ("device", disk.name.as_str()),
+                ("mount_point", disk.mount_point.as_str()),
+            ];
+            let spaces = [
+                (&DISK_FREE, disk.free_space),
+                (&DISK_USED, disk.used_space),
+                (&DISK_TOTAL, disk.total_space),
+            ];
+            for (family, space) in spaces {
+                if let Some(space) = space {
+                    samples.push(family, &labels, space as f64);
+                }
+            }
+        }
+    }
+    if let Some(io) = &data.io {
+        let mut devices: Vec<_> = io.iter().collect();
+        devices.sort_by(|a, b| a.0.cmp(b.0));
+        for (device, io_data) in devices {
+            if let Some(io_data) = io_data {
+                let labels = [("device", device.as_str())];
+                samples.push(&DISK_READ, &labels, io_data.read_bytes as f64);
+                samples.push(&DISK_WRITTEN, &labels, io_data.write_bytes as f64);
+            }
+        }
+    }
+
+    // The network harvester counts bits.
+    if let Some(network) = &data.network {
+        samples.push(&NETWORK_RECEIVE, &[], network.total_rx as f64 / 8.0);
+        samples.push(&NETWORK_TRANSMIT, &[], network.total_tx as f64 / 8.0);
+        samples.push(&NETWORK_RECEIVE_RATE, &[], network.rx as f64 / 8.0);
+        samples.push(&NETWORK_TRANSMIT_RATE, &[], network.tx as f64 / 8.0);
+    }
+
+    if let Some(sensors) = &data.temperature_sensors {
+        for sensor in sensors {
+            samples.push(
+                &TEMPERATURE,
+                &[("sensor", &sensor.name)],
+                sensor.temperature as f64,
+            );
+        }
+    }
+
+    #[cfg(feature = "battery")]
+    if let Some(batteries) = &data.list_of_batteries {
+        for (index, battery) in batteries.iter().enumerate() {
+            let index = index.to_string();
+            let labels = [("battery", index.as_str())];
+            samples.push(&BATTERY_CHARGE, &labels, battery.charge_percent / 100.0);
+            samples.push(&BATTERY_HEALTH, &labels, battery.health_percent / 100.0);
+            samples.push(
+                &BATTERY_POWER,
+                &labels,
+                battery.power_consumption_rate_watts,
+            );
+            // There's only an estimate of when it'll be empty while it's discharging.
+            let discharging = battery.secs_until_empty.is_some();
+            samples.push(&BATTERY_DISCHARGING, &labels, discharging as u8 as f64);
+            if let Some(secs) = battery.secs_until_full {
+                samples.push(&BATTERY_TIME_TO_FULL, &labels, secs as f64);
+            }
+            if let Some(secs) = battery.secs_until_empty {
+                samples.push(&BATTERY_TIME_TO_EMPTY, &labels, secs as f64);
+            }
+        }
+    }
+
+    if let Some(processes) = &data.list_of_processes {
+        samples.push(&PROCESSES, &[], processes.len() as f64);
+        let read_rate: u64 = processes
+            .iter()
+            .map(|process| process.read_bytes_per_sec)
+            .sum();
+        let write_rate: u64 = processes
+            .iter()
+            .map(|process| process.write_bytes_per_sec)
+            .sum();
+        samples.push(&PROCESSES_READ_RATE, &[], read_rate as f64);
+        samples.push(&PROCESSES_WRITE_RATE, &[], write_rate as f64);
+        if include_processes {
+            for process in processes {
+                let pid = process.pid.to_string();
+                let labels = [("pid", pid.as_str()), ("name", process.name.as_str())];
+                let cpu_usage = process.cpu_usage_percent / 100.0;
+                samples.push(&PROCESS_CPU_USAGE, &labels, cpu_usage);
+                samples.push(&PROCESS_MEMORY, &labels, process.mem_usage_bytes as f64);
+                samples.push(&PROCESS_READ, &labels, process.total_read_bytes as f64);
+                samples.push(&PROCESS_WRITTEN, &labels, process.total_write_bytes as f64);
+            }
+        }
+    }
+
+    if data.refreshed_at.contains_key(&Subsystem::Host) {
+        samples.push(&UPTIME, &[], data.uptime.as_secs_f64());
+        let host_labels = [
+            ("hostname", &data.hostname),
+            ("kernel_name", &data.kernel_name),
+            ("kernel_version", &data.kernel_version),
+            ("os_version", &data.os_version),
+        ];
+        let host_labels: Vec<_> = host_labels
+            .iter()
+            .filter_map(|(name, value)| Some((*name, value.as_deref()?)))
+            .collect();
+        samples.push(&HOST, &host_labels, 1.0);
+    }
+
+    samples.0
+}
+
+/// Renders samples in the OpenMetrics text format.
+pub fn render_openmetrics(samples: &[Sample]) -> String {
+    let mut out = String::new();
+    for family in FAMILIES {
+        let mut family_samples = samples
+            .iter()
+            .filter(|sample| std::ptr::eq(sample.family, *family))
+            .peekable();
+        if family_samples.peek().is_none() {
+            continue;
+        }
+
+        let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind.as_str());
+        if let Some(unit) = family.unit {
+            let _ = writeln!(out, "# UNIT {} {}", family.name, unit);
+        }
+        let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+        for sample in family_samples {
+            out.push_str(&sample.id());
+            out.push(' ');
+            write_value(&mut out, sample.value);
+            out.push('\n');
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn write_labels(out: &mut String, labels: &[(&'static str, String)]) {
+    if labels.is_empty() {
+        return;
+    }
+
+    out.push('{');
+    for (i, (name, value)) in labels.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(name);
+        out.push_str("=\"");
+        for c in value.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: f64) {
+    if value.is_nan() {
+        out.push_str("NaN");
+    } else if value.is_infinite() {
+        out.push_str(if value > 0.0 { "+Inf" } else { "-Inf" });
+    } else {
+        let _ = write!(out, "{}", value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_harvester::{cpu::CpuData, disks::DiskHarvest, network::NetworkHarvest};
+
+    #[test]
+    fn test_render_openmetrics() {
+        let data = Data {
+            cpu: Some(vec![
+                CpuData {
+                    data_type: CpuDataType::Cpu(0),
+                    cpu_usage: 50.0,
+                },
+                CpuData {
+                    data_type: CpuDataType::Cpu(1),
+                    cpu_usage: 25.0,
+                },
+            ]),
+            network: Some(NetworkHarvest {
+                rx: 800,
+                tx: 0,
+                total_rx: 8000,
+                total_tx: 16,
+            }),
+            disks: Some(vec![DiskHarvest {
+                name: "/dev/sda1".to_string(),
+                mount_point: "/mnt/\"quoted\"".to_string(),
+                free_space: Some(1024),
+                used_space: None,
+                total_space: None,
+            }]),
+            ..Data::default()
+        };
+
+        assert_eq!(
+            render_openmetrics(&samples(&data, false)),
+            r#"# TYPE toerings_cpu_usage_ratio gauge
+# UNIT toerings_cpu_usage_ratio ratio
+# HELP toerings_cpu_usage_ratio Usage of each CPU core.
+toerings_cpu_usage_ratio{cpu="0"} 0.5
+toerings_cpu_usage_ratio{cpu="1"} 0.25
+# TYPE toerings_cpu_average_usage_ratio gauge
+# UNIT toerings_cpu_average_usage_ratio ratio
+# HELP toerings_cpu_average_usage_ratio Usage averaged over all CPU cores.
+toerings_cpu_average_usage_ratio 0.375
+# TYPE toerings_disk_free_bytes gauge
+# UNIT toerings_disk_free_bytes bytes
+# HELP toerings_disk_free_bytes Free space on each mounted disk.
+toerings_disk_free_bytes{device="/dev/sda1",mount_point="/mnt/\"quoted\""} 1024
+# TYPE toerings_network_receive_bytes counter
+# UNIT toerings_network_receive_bytes bytes
+# HELP toerings_network_receive_bytes Bytes received over all network interfaces.
+toerings_network_receive_bytes_total 1000
+# TYPE toerings_network_transmit_bytes counter
+# UNIT toerings_network_transmit_bytes bytes
+# HELP toerings_network_transmit_bytes Bytes sent over all network interfaces.
+toerings_network_transmit_bytes_total 2
+# TYPE toerings_network_receive_bytes_per_second gauge
+# UNIT toerings_network_receive_bytes_per_second bytes_per_second
+# HELP toerings_network_receive_bytes_per_second Bytes received per second over all network interfaces.
+toerings_network_receive_bytes_per_second 100
+# TYPE toerings_network_transmit_bytes_per_second gauge
+# UNIT toerings_network_transmit_bytes_per_second bytes_per_second
+# HELP toerings_network_transmit_bytes_per_second Bytes sent per second over all network interfaces.
+toerings_network_transmit_bytes_per_second 0
+# EOF
+"#
+        );
+    }
+}
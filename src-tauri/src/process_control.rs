@@ -0,0 +1,348 @@
+//! Acting on a process from the process list: sending it a signal, or changing its nice value, CPU
+//! affinity or IO priority.
+//!
+//! Each command takes the process' start time as well as its pid, as shown in the list, and does
+//! nothing if they no longer match. Otherwise, if the process had exited since the list was
+//! collected, its pid could have gone to another process, which would be hit instead. Process 1
+//! and ToeRings itself are refused unless `force` is set.
+//!
+//! Signals and nice values are supported on Unix, and CPU affinity and IO priority on Linux only.
+
+use serde::Deserialize;
+
+use crate::utils::error::{self, ToeError};
+use crate::Pid;
+
+/// How far apart the start times can be for the same process. On Linux, it's worked out from the
+/// boot time in `/proc/stat`, which moves by a second now and then.
+pub const START_TIME_SLACK_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoPriorityClass {
+    /// Served before everything else. Needs root.
+    Realtime,
+    BestEffort,
+    /// Only served when nothing else needs the disk.
+    Idle,
+}
+
+#[allow(dead_code)]
+fn unsupported(action: &str) -> ToeError {
+    ToeError::ProcessError(format!("can't {} processes on this platform", action))
+}
+
+/// When the live process `pid` started, in the same terms as [`ProcessHarvest::start_time`].
+///
+/// [`ProcessHarvest::start_time`]: crate::data_harvester::processes::ProcessHarvest::start_time
+#[cfg(target_os = "linux")]
+fn current_start_time(pid: Pid) -> Option<u64> {
+    use crate::data_harvester::processes::{read_boot_time, start_time_millis};
+
+    let stat = procfs::process::Process::new(pid).ok()?.stat().ok()?;
+    let boot_time = read_boot_time(std::path::Path::new("/")).ok()?;
+    Some(start_time_millis(&stat, boot_time))
+}
+
+#[cfg(all(target_family = "unix", not(target_os = "linux")))]
+fn current_start_time(pid: Pid) -> Option<u64> {
+    use sysinfo::{PidExt, ProcessExt, System, SystemExt};
+
+    let pid = sysinfo::Pid::from_u32(pid as u32);
+    let mut sys = System::new();
+    sys.refresh_process(pid);
+    sys.process(pid).map(|process| process.start_time() * 1000)
+}
+
+/// Makes sure that `pid` is still the process that started at `start_time`, and that it's one that
+/// can be acted on.
+#[cfg(target_family = "unix")]
+fn check_target(pid: Pid, start_time: u64, force: bool) -> error::Result<()> {
+    // Zero and negative pids stand for process groups, or for every process there is.
+    if pid <= 0 {
+        return Err(ToeError::ProcessError(format!(
+            "{} isn't the pid of a process",
+            pid
+        )));
+    }
+    if !force {
+        if pid == 1 {
+            return Err(ToeError::ProcessError(
+                "process 1 is init, which the whole system depends on".to_string(),
+            ));
+        }
+        if pid as u32 == std::process::id() {
+            return Err(ToeError::ProcessError(format!(
+                "process {} is ToeRings itself",
+                pid
+            )));
+        }
+    }
+
+    match current_start_time(pid) {
+        Some(current) if current.abs_diff(start_time) <= START_TIME_SLACK_MS => Ok(()),
+        _ => Err(ToeError::ProcessError(format!(
+            "process {} has exited",
+            pid
+        ))),
+    }
+}
+
+/// Turns the error from the last call into the libc into a [`ToeError`], saying what couldn't be
+/// done.
+#[cfg(target_family = "unix")]
+fn last_os_error(action: &str, pid: Pid) -> ToeError {
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EPERM) | Some(libc::EACCES) => ToeError::PermissionDenied(format!(
+            "not allowed to {} process {}, as it belongs to another user or this needs root",
+            action, pid
+        )),
+        Some(libc::ESRCH) => ToeError::ProcessError(format!("process {} has exited", pid)),
+        _ => ToeError::InvalidIo(format!("couldn't {} process {}: {}", action, pid, err)),
+    }
+}
+
+/// Parses a signal like `TERM`, `SIGTERM` or `15`.
+#[cfg(target_family = "unix")]
+fn parse_signal(signal: &str) -> error::Result<libc::c_int> {
+    if let Ok(number) = signal.parse::<libc::c_int>() {
+        return if number > 0 {
+            Ok(number)
+        } else {
+            Err(ToeError::ProcessError(format!("{} isn't a signal", number)))
+        };
+    }
+
+    let name = signal.to_ascii_uppercase();
+    let number = match name.strip_prefix("SIG").unwrap_or(&name) {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "ILL" => libc::SIGILL,
+        "TRAP" => libc::SIGTRAP,
+        "ABRT" => libc::SIGABRT,
+        "BUS" => libc::SIGBUS,
+        "FPE" => libc::SIGFPE,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "SEGV" => libc::SIGSEGV,
+        "USR2" => libc::SIGUSR2,
+        "PIPE" => libc::SIGPIPE,
+        "ALRM" => libc::SIGALRM,
+        "TERM" => libc::SIGTERM,
+        "CHLD" => libc::SIGCHLD,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        "TSTP" => libc::SIGTSTP,
+        "TTIN" => libc::SIGTTIN,
+        "TTOU" => libc::SIGTTOU,
+        "URG" => libc::SIGURG,
+        "XCPU" => libc::SIGXCPU,
+        "XFSZ" => libc::SIGXFSZ,
+        "VTALRM" => libc::SIGVTALRM,
+        "PROF" => libc::SIGPROF,
+        "WINCH" => libc::SIGWINCH,
+        "IO" => libc::SIGIO,
+        "SYS" => libc::SIGSYS,
+        _ => {
+            return Err(ToeError::ProcessError(format!(
+                "unknown signal {:?}",
+                signal
+            )))
+        }
+    };
+    Ok(number)
+}
+
+/// Sends `signal` to a process, e.g. `TERM`, `KILL`, `STOP` or `CONT`. Any signal can be given by
+/// its number instead.
+#[tauri::command]
+pub fn send_signal(
+    pid: Pid,
+    start_time: u64,
+    signal: String,
+    force: Option<bool>,
+) -> error::Result<()> {
+    #[cfg(target_family = "unix")]
+    {
+        let signal_number = parse_signal(&signal)?;
+        check_target(pid, start_time, force.unwrap_or(false))?;
+        // SAFETY: kill only reads its arguments.
+        if unsafe { libc::kill(pid, signal_number) } != 0 {
+            return Err(last_os_error(&format!("send {} to", signal), pid));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_family = "unix"))]
+    {
+        let _ = (pid, start_time, signal, force);
+        Err(unsupported("signal"))
+    }
+}
+
+/// Sets the nice value of a process, from -20 for the highest priority to 19 for the lowest.
+/// Anything below the current value needs root.
+#[tauri::command]
+pub fn set_nice(pid: Pid, start_time: u64, nice: i32, force: Option<bool>) -> error::Result<()> {
+    #[cfg(target_family = "unix")]
+    {
+        if !(-20..=19).contains(&nice) {
+            return Err(ToeError::ProcessError(format!(
+                "the nice value has to be from -20 to 19, not {}",
+                nice
+            )));
+        }
+        check_target(pid, start_time, force.unwrap_or(false))?;
+        // SAFETY: setpriority only reads its arguments.
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, pid as libc::id_t, nice) } != 0 {
+            return Err(last_os_error("renice", pid));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_family = "unix"))]
+    {
+        let _ = (pid, start_time, nice, force);
+        Err(unsupported("renice"))
+    }
+}
+
+/// Restricts a process to the CPUs numbered in `cpus`, counting from 0.
+#[tauri::command]
+pub fn set_cpu_affinity(
+    pid: Pid,
+    start_time: u64,
+    cpus: Vec<usize>,
+    force: Option<bool>,
+) -> error::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        const MAX_CPUS: usize = libc::CPU_SETSIZE as usize;
+
+        if cpus.is_empty() {
+            return Err(ToeError::ProcessError(
+                "a process needs at least one CPU to run on".to_string(),
+            ));
+        }
+        if let Some(cpu) = cpus.iter().find(|cpu| **cpu >= MAX_CPUS) {
+            return Err(ToeError::ProcessError(format!("there's no CPU {}", cpu)));
+        }
+        check_target(pid, start_time, force.unwrap_or(false))?;
+
+        // SAFETY: cpu_set_t is a plain bit mask, for which all zeroes is the empty set, and every
+        // CPU set in it is below CPU_SETSIZE.
+        let result = unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            for cpu in &cpus {
+                libc::CPU_SET(*cpu, &mut set);
+            }
+            libc::sched_setaffinity(pid, std::mem::size_of::<libc::cpu_set_t>(), &set)
+        };
+        if result != 0 {
+            if std::io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL) {
+                return Err(ToeError::ProcessError(
+                    "none of those CPUs are online".to_string(),
+                ));
+            }
+            return Err(last_os_error("set the CPU affinity of", pid));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (pid, start_time, cpus, force);
+        Err(unsupported("set the CPU affinity of"))
+    }
+}
+
+/// Sets the IO scheduling class of a process, and its priority within that class from 0 for the
+/// highest to 7 for the lowest. The priority doesn't apply to the idle class.
+#[tauri::command]
+pub fn set_io_priority(
+    pid: Pid,
+    start_time: u64,
+    class: IoPriorityClass,
+    priority: Option<u8>,
+    force: Option<bool>,
+) -> error::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        // From linux/ioprio.h.
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_SHIFT: u32 = 13;
+
+        let class_number = match class {
+            IoPriorityClass::Realtime => 1,
+            IoPriorityClass::BestEffort => 2,
+            IoPriorityClass::Idle => 3,
+        };
+        let priority = match (class, priority) {
+            (IoPriorityClass::Idle, _) => 0,
+            (_, Some(priority)) if priority > 7 => {
+                return Err(ToeError::ProcessError(format!(
+                    "the IO priority has to be from 0 to 7, not {}",
+                    priority
+                )))
+            }
+            (_, priority) => priority.unwrap_or(4),
+        };
+        check_target(pid, start_time, force.unwrap_or(false))?;
+
+        let ioprio = (class_number << IOPRIO_CLASS_SHIFT) | libc::c_int::from(priority);
+        // SAFETY: ioprio_set only reads its arguments.
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, pid, ioprio) } != 0 {
+            return Err(last_os_error("set the IO priority of", pid));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (pid, start_time, class, priority, force);
+        Err(unsupported("set the IO priority of"))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_control() {
+        assert_eq!(parse_signal("term"), Ok(libc::SIGTERM));
+        assert_eq!(parse_signal("SIGSTOP"), Ok(libc::SIGSTOP));
+        assert_eq!(parse_signal("34"), Ok(34));
+        assert!(parse_signal("0").is_err());
+        assert!(parse_signal("SIGNIFICANT").is_err());
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = child.id() as Pid;
+        let start_time = current_start_time(pid).unwrap();
+
+        // As if the pid had been reused since.
+        assert!(matches!(
+            send_signal(pid, start_time - 60_000, "KILL".to_string(), None),
+            Err(ToeError::ProcessError(_))
+        ));
+        assert!(matches!(
+            send_signal(1, 0, "TERM".to_string(), None),
+            Err(ToeError::ProcessError(_))
+        ));
+        let own_pid = std::process::id() as Pid;
+        let own_start_time = current_start_time(own_pid).unwrap();
+        assert!(matches!(
+            set_nice(own_pid, own_start_time, 5, None),
+            Err(ToeError::ProcessError(_))
+        ));
+
+        set_nice(pid, start_time, 10, None).unwrap();
+        let nice = unsafe { libc::getpriority(libc::PRIO_PROCESS as _, pid as libc::id_t) };
+        assert_eq!(nice, 10);
+        set_io_priority(pid, start_time, IoPriorityClass::Idle, None, None).unwrap();
+
+        send_signal(pid, start_time, "KILL".to_string(), None).unwrap();
+        child.wait().unwrap();
+    }
+}
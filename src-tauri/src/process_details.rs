@@ -0,0 +1,340 @@
+//! Everything about a single process that's too much to collect for all of them on every refresh,
+//! for when it's picked out of the process list.
+//!
+//! Each section is read on its own, so one that can't be read, usually because the process
+//! belongs to another user, comes back as an error in its place rather than failing the rest. Only
+//! on Linux.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::data_harvester::cgroups::CgroupOwner;
+#[cfg(target_os = "linux")]
+use crate::data_harvester::processes::MemoryRollup;
+use crate::utils::error::{self, ToeError};
+use crate::Pid;
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct ProcessDetails {
+    pub pid: Pid,
+    pub name: String,
+    /// In the same terms as [`ProcessHarvest::start_time`], to tell whether this is still the
+    /// process from the list.
+    ///
+    /// [`ProcessHarvest::start_time`]: crate::data_harvester::processes::ProcessHarvest::start_time
+    pub start_time: u64,
+    pub exe: error::Result<String>,
+    pub cwd: error::Result<String>,
+    /// Every argument, with the first being the command as it was run.
+    pub argv: error::Result<Vec<String>>,
+    /// Only readable for processes of the same user, or as root.
+    pub environ: error::Result<BTreeMap<String, String>>,
+    pub limits: error::Result<Vec<ResourceLimit>>,
+    pub open_files: error::Result<Vec<OpenFile>>,
+    #[cfg(target_os = "linux")]
+    pub memory: error::Result<MemoryRollup>,
+    /// `None` if the kernel doesn't have cgroups.
+    pub cgroup: error::Result<Option<ProcessCgroup>>,
+    /// The inode of each namespace the process is in, by type, like `net` or `pid`. Processes in
+    /// the same namespace have the same inode.
+    pub namespaces: error::Result<BTreeMap<String, u64>>,
+    /// The parent first, then its parent, and so on up to init.
+    pub parents: error::Result<Vec<ParentProcess>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct ResourceLimit {
+    /// The way `/proc/<pid>/limits` names it, like `Max open files`.
+    pub name: &'static str,
+    /// `None` for no limit.
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+    /// What the limit is in, like `bytes`. `None` for a count of what it's named after.
+    pub unit: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub enum FileKind {
+    /// A file, directory or device.
+    File,
+    Socket,
+    Pipe,
+    /// Something the kernel hands out a descriptor for without a file behind it, like an eventfd,
+    /// epoll or inotify instance.
+    AnonInode,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct OpenFile {
+    pub fd: i32,
+    pub kind: FileKind,
+    /// The path, or what the kernel shows in place of one, like `socket:[31337]`.
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct ProcessCgroup {
+    pub path: String,
+    pub owner: Option<CgroupOwner>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub struct ParentProcess {
+    pub pid: Pid,
+    pub name: String,
+}
+
+/// Turns why a section couldn't be read into a [`ToeError`], keeping not being allowed to read it
+/// apart from everything else.
+#[cfg(target_os = "linux")]
+fn section_error(pid: Pid, section: &str, err: procfs::ProcError) -> ToeError {
+    use procfs::ProcError;
+
+    match err {
+        ProcError::PermissionDenied(_) => ToeError::PermissionDenied(format!(
+            "not allowed to read the {} of process {}, as it belongs to another user",
+            section, pid
+        )),
+        ProcError::NotFound(_) => ToeError::ProcessError(format!("process {} has exited", pid)),
+        err => err.into(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_limits(process: &procfs::process::Process) -> procfs::ProcResult<Vec<ResourceLimit>> {
+    use procfs::process::{Limit, LimitValue};
+
+    let value = |value: LimitValue| match value {
+        LimitValue::Unlimited => None,
+        LimitValue::Value(value) => Some(value),
+    };
+    let limit = |name, unit, limit: Limit| ResourceLimit {
+        name,
+        soft: value(limit.soft_limit),
+        hard: value(limit.hard_limit),
+        unit,
+    };
+
+    let bytes = Some("bytes");
+    let limits = process.limits()?;
+    Ok(vec![
+        limit("Max cpu time", Some("seconds"), limits.max_cpu_time),
+        limit("Max file size", bytes, limits.max_file_size),
+        limit("Max data size", bytes, limits.max_data_size),
+        limit("Max stack size", bytes, limits.max_stack_size),
+        limit("Max core file size", bytes, limits.max_core_file_size),
+        limit("Max resident set", bytes, limits.max_resident_set),
+        limit("Max processes", None, limits.max_processes),
+        limit("Max open files", None, limits.max_open_files),
+        limit("Max locked memory", bytes, limits.max_locked_memory),
+        limit("Max address space", bytes, limits.max_address_space),
+        limit("Max file locks", None, limits.max_file_locks),
+        limit("Max pending signals", None, limits.max_pending_signals),
+        limit("Max msgqueue size", bytes, limits.max_msgqueue_size),
+        limit("Max nice priority", None, limits.max_nice_priority),
+        limit("Max realtime priority", None, limits.max_realtime_priority),
+        limit(
+            "Max realtime timeout",
+            Some("us"),
+            limits.max_realtime_timeout,
+        ),
+    ])
+}
+
+#[cfg(target_os = "linux")]
+fn read_open_files(process: &procfs::process::Process) -> procfs::ProcResult<Vec<OpenFile>> {
+    use procfs::process::FDTarget;
+
+    let mut open_files: Vec<OpenFile> = process
+        .fd()?
+        // A descriptor can be closed between listing it and reading where it points.
+        .flatten()
+        .map(|fd| {
+            let (kind, target) = match fd.target {
+                FDTarget::Path(path) => (FileKind::File, path.display().to_string()),
+                FDTarget::MemFD(name) => (FileKind::File, format!("/memfd:{}", name)),
+                FDTarget::Socket(inode) | FDTarget::Net(inode) => {
+                    (FileKind::Socket, format!("socket:[{}]", inode))
+                }
+                FDTarget::Pipe(inode) => (FileKind::Pipe, format!("pipe:[{}]", inode)),
+                FDTarget::AnonInode(name) => (FileKind::AnonInode, format!("anon_inode:{}", name)),
+                FDTarget::Other(name, inode) => (FileKind::Other, format!("{}:[{}]", name, inode)),
+            };
+            OpenFile {
+                fd: fd.fd,
+                kind,
+                target,
+            }
+        })
+        .collect();
+    open_files.sort_unstable_by_key(|open_file| open_file.fd);
+    Ok(open_files)
+}
+
+#[cfg(target_os = "linux")]
+fn read_parents(mut parent_pid: Pid) -> procfs::ProcResult<Vec<ParentProcess>> {
+    let mut parents = Vec::new();
+    // Init's parent is 0, as are those of processes whose parent is outside their pid namespace.
+    // The limit is only in case a parent exits while this runs and its pid is taken by one of its
+    // descendants, which would make a loop.
+    while parent_pid > 0 && parents.len() < 1024 {
+        let stat = procfs::process::Process::new(parent_pid)?.stat()?;
+        parents.push(ParentProcess {
+            pid: parent_pid,
+            name: stat.comm,
+        });
+        parent_pid = stat.ppid;
+    }
+    Ok(parents)
+}
+
+/// Reads everything about the process `pid` for the detail view. Only fails if the process isn't
+/// there; otherwise, each section that can't be read has an error of its own.
+#[tauri::command]
+pub fn get_process_details(pid: Pid) -> error::Result<ProcessDetails> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::path::Path;
+
+        use crate::data_harvester::cgroups::{owner_of, parse_cgroup_file};
+        use crate::data_harvester::processes::{
+            parse_smaps_rollup, read_boot_time, start_time_millis,
+        };
+
+        let exited = || ToeError::ProcessError(format!("process {} has exited", pid));
+        let process = procfs::process::Process::new(pid).map_err(|_| exited())?;
+        let stat = process.stat().map_err(|_| exited())?;
+        let read_proc_file = |name: &str| -> procfs::ProcResult<String> {
+            Ok(std::fs::read_to_string(format!("/proc/{}/{}", pid, name))?)
+        };
+        let section = |name: &str, err| section_error(pid, name, err);
+
+        Ok(ProcessDetails {
+            pid,
+            start_time: start_time_millis(&stat, read_boot_time(Path::new("/"))?),
+            name: stat.comm,
+            exe: process
+                .exe()
+                .map(|exe| exe.display().to_string())
+                .map_err(|err| section("executable", err)),
+            cwd: process
+                .cwd()
+                .map(|cwd| cwd.display().to_string())
+                .map_err(|err| section("working directory", err)),
+            argv: process.cmdline().map_err(|err| section("arguments", err)),
+            environ: process
+                .environ()
+                .map(|environ| {
+                    environ
+                        .into_iter()
+                        .map(|(name, value)| {
+                            (
+                                name.to_string_lossy().into_owned(),
+                                value.to_string_lossy().into_owned(),
+                            )
+                        })
+                        .collect()
+                })
+                .map_err(|err| section("environment", err)),
+            limits: read_limits(&process).map_err(|err| section("resource limits", err)),
+            open_files: read_open_files(&process).map_err(|err| section("open files", err)),
+            memory: read_proc_file("smaps_rollup")
+                .map(|contents| parse_smaps_rollup(&contents))
+                .map_err(|err| section("memory maps", err)),
+            cgroup: read_proc_file("cgroup")
+                .map(|contents| {
+                    parse_cgroup_file(&contents).map(|path| ProcessCgroup {
+                        owner: owner_of(&path),
+                        path,
+                    })
+                })
+                .map_err(|err| section("cgroup", err)),
+            namespaces: process
+                .namespaces()
+                .map(|namespaces| {
+                    namespaces
+                        .into_iter()
+                        .map(|(name, namespace)| {
+                            (name.to_string_lossy().into_owned(), namespace.identifier)
+                        })
+                        .collect()
+                })
+                .map_err(|err| section("namespaces", err)),
+            parents: read_parents(stat.ppid).map_err(|err| section("parents", err)),
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        Err(ToeError::ProcessError(
+            "can't inspect processes on this platform".to_string(),
+        ))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    #[test]
+    fn test_process_details() {
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .env("TOERINGS_TEST", "details")
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pid = child.id() as Pid;
+        // Until the child has finished `exec`, it has this test's arguments, or none at all.
+        let mut details = get_process_details(pid).unwrap();
+        for _ in 0..100 {
+            if matches!(&details.argv, Ok(argv) if argv == &["sleep", "30"]) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            details = get_process_details(pid).unwrap();
+        }
+
+        assert_eq!(details.name, "sleep");
+        assert_eq!(details.argv.unwrap(), ["sleep", "30"]);
+        assert!(details.exe.unwrap().ends_with("sleep"));
+        assert_eq!(
+            details
+                .environ
+                .unwrap()
+                .get("TOERINGS_TEST")
+                .map(String::as_str),
+            Some("details")
+        );
+        let open_files = details.open_files.unwrap();
+        assert_eq!(open_files[0].fd, 0);
+        assert_eq!(open_files[0].kind, FileKind::Pipe);
+        assert!(details
+            .limits
+            .unwrap()
+            .iter()
+            .any(|limit| limit.name == "Max open files"));
+        assert!(details.namespaces.unwrap().contains_key("pid"));
+        let parents = details.parents.unwrap();
+        assert_eq!(parents[0].pid, std::process::id() as Pid);
+        assert_eq!(parents.last().unwrap().pid, 1);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(matches!(
+            get_process_details(pid),
+            Err(ToeError::ProcessError(_))
+        ));
+    }
+}
@@ -0,0 +1,236 @@
+//! Keeps a log of the processes that started and exited, so that one that came and went between
+//! looks at the process list, like a build step that pegged the CPU for a few seconds, can still be
+//! found afterwards.
+//!
+//! Processes are only seen when the list is refreshed, so the times are those of the refresh that
+//! noticed, and a process that started and exited between two refreshes is missed entirely.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use fxhash::FxHashMap;
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use crate::data_harvester::{processes::ProcessHarvest, Data, Subsystem};
+use crate::process_control::START_TIME_SLACK_MS;
+use crate::Pid;
+
+/// How many of the latest events are kept for [`get_process_events`].
+const HISTORY_LEN: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessEventKind {
+    Started,
+    Exited,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessEvent {
+    pub kind: ProcessEventKind,
+    /// When the process list that showed it was collected, in milliseconds since the Unix epoch.
+    pub time: u64,
+    pub pid: Pid,
+    pub name: String,
+    pub command: String,
+    /// `None` where processes aren't listed with their users.
+    pub user: Option<String>,
+    /// In the same terms as [`ProcessHarvest::start_time`].
+    pub start_time: u64,
+    /// How long the process ran for, in milliseconds, up to when it was noticed to have exited.
+    /// Only for exits.
+    pub lifetime_millis: Option<u64>,
+    /// The most CPU and memory it was seen using. For starts, that's only at the first refresh.
+    pub peak_cpu_usage_percent: f64,
+    pub peak_mem_usage_bytes: u64,
+}
+
+impl ProcessEvent {
+    fn started(process: &ProcessHarvest, time: u64) -> Self {
+        #[cfg(target_family = "unix")]
+        let user = Some(process.user.to_string());
+        #[cfg(not(target_family = "unix"))]
+        let user = None;
+
+        ProcessEvent {
+            kind: ProcessEventKind::Started,
+            time,
+            pid: process.pid,
+            name: process.name.clone(),
+            command: process.command.clone(),
+            user,
+            start_time: process.start_time,
+            lifetime_millis: None,
+            peak_cpu_usage_percent: process.cpu_usage_percent,
+            peak_mem_usage_bytes: process.mem_usage_bytes,
+        }
+    }
+
+    fn exited(mut self, time: u64) -> Self {
+        self.kind = ProcessEventKind::Exited;
+        self.time = time;
+        self.lifetime_millis = Some(time.saturating_sub(self.start_time));
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ProcessEvents {
+    /// What's known about each running process, as the event for when it exits.
+    running: FxHashMap<Pid, ProcessEvent>,
+    /// When the last process list that was compared was collected. `None` before the first.
+    last_refresh: Option<u64>,
+    history: VecDeque<ProcessEvent>,
+}
+
+impl ProcessEvents {
+    /// Compares `processes`, collected at `time`, with the last list, returning the processes that
+    /// started and exited in between. The first list only says what's running, as the processes in
+    /// it didn't just start.
+    pub fn update(&mut self, processes: &[ProcessHarvest], time: u64) -> Vec<ProcessEvent> {
+        let mut events = Vec::new();
+        let mut previous = std::mem::take(&mut self.running);
+        for process in processes {
+            let seen = previous.remove(&process.pid);
+            let running = match seen {
+                // The pid could have been reused since.
+                Some(seen)
+                    if seen.start_time.abs_diff(process.start_time) <= START_TIME_SLACK_MS =>
+                {
+                    ProcessEvent {
+                        peak_cpu_usage_percent: seen
+                            .peak_cpu_usage_percent
+                            .max(process.cpu_usage_percent),
+                        peak_mem_usage_bytes: seen
+                            .peak_mem_usage_bytes
+                            .max(process.mem_usage_bytes),
+                        ..seen
+                    }
+                }
+                seen => {
+                    events.extend(seen.map(|seen| seen.exited(time)));
+                    let started = ProcessEvent::started(process, time);
+                    if self.last_refresh.is_some() {
+                        events.push(started.clone());
+                    }
+                    started
+                }
+            };
+            self.running.insert(process.pid, running);
+        }
+
+        // Whatever's left wasn't in this list.
+        let mut exited: Vec<ProcessEvent> = previous
+            .into_values()
+            .map(|process| process.exited(time))
+            .collect();
+        exited.sort_unstable_by_key(|process| process.pid);
+        events.extend(exited);
+
+        self.last_refresh = Some(time);
+        self.history.extend(events.iter().cloned());
+        let excess = self.history.len().saturating_sub(HISTORY_LEN);
+        self.history.drain(..excess);
+        events
+    }
+}
+
+#[derive(Default)]
+pub struct ProcessEventsState(Mutex<ProcessEvents>);
+
+impl ProcessEventsState {
+    /// Looks for processes that started or exited since the last snapshot, letting the frontend
+    /// know about each with a `processEvent` event.
+    pub fn check(&self, app_handle: &AppHandle, data: &Data) {
+        let Some(processes) = &data.list_of_processes else {
+            return;
+        };
+        let Some(&refreshed_at) = data.refreshed_at.get(&Subsystem::Processes) else {
+            return;
+        };
+        let mut events = self.0.lock().unwrap();
+        // The list is only refreshed as often as its interval, and left as it was in between.
+        if events.last_refresh == Some(refreshed_at) {
+            return;
+        }
+        for event in events.update(processes, refreshed_at) {
+            let _ = app_handle.emit_all("processEvent", &event);
+        }
+    }
+}
+
+/// Returns the latest processes that started or exited, oldest first. With `since`, in
+/// milliseconds since the Unix epoch, only those noticed after then.
+#[tauri::command]
+pub fn get_process_events(
+    events: State<ProcessEventsState>,
+    since: Option<u64>,
+) -> Vec<ProcessEvent> {
+    let events = events.0.lock().unwrap();
+    events
+        .history
+        .iter()
+        .filter(|event| since.map_or(true, |since| event.time > since))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_events() {
+        use ProcessEventKind::{Exited, Started};
+
+        let mut events = ProcessEvents::default();
+        let mut kinds = |processes: &[ProcessHarvest], time: u64| -> Vec<(ProcessEventKind, Pid)> {
+            let events = events.update(processes, time * 1000);
+            events.iter().map(|event| (event.kind, event.pid)).collect()
+        };
+
+        let init = ProcessHarvest::for_test(1, "init");
+        let make = ProcessHarvest {
+            start_time: 99_500,
+            cpu_usage_percent: 80.0,
+            mem_usage_bytes: 300,
+            ..ProcessHarvest::for_test(50, "make")
+        };
+
+        // What was already running didn't just start.
+        assert_eq!(kinds(std::slice::from_ref(&init), 100), []);
+        assert_eq!(kinds(&[init.clone(), make.clone()], 101), [(Started, 50)]);
+        // Start times that are a little off are put down to rounding.
+        let init_rounded = ProcessHarvest {
+            start_time: 500,
+            ..init.clone()
+        };
+        let make_busier = ProcessHarvest {
+            cpu_usage_percent: 95.0,
+            mem_usage_bytes: 200,
+            ..make
+        };
+        assert_eq!(kinds(&[init_rounded, make_busier], 102), []);
+        assert_eq!(kinds(std::slice::from_ref(&init), 103), [(Exited, 50)]);
+        // The same pid again, for a process that started later.
+        let make_again = |start_time| ProcessHarvest {
+            start_time,
+            ..ProcessHarvest::for_test(50, "make")
+        };
+        assert_eq!(
+            kinds(&[init.clone(), make_again(103_800)], 104),
+            [(Started, 50)]
+        );
+        assert_eq!(
+            kinds(&[init, make_again(104_900)], 105),
+            [(Exited, 50), (Started, 50)]
+        );
+
+        let exited = &events.history[1];
+        assert_eq!(exited.kind, Exited);
+        assert_eq!(exited.lifetime_millis, Some(3500));
+        assert_eq!(exited.peak_cpu_usage_percent, 95.0);
+        assert_eq!(exited.peak_mem_usage_bytes, 300);
+        assert_eq!(events.history.len(), 5);
+    }
+}
@@ -0,0 +1,328 @@
+//! The process list as a tree, with the usage of each process added up with its descendants', so a
+//! browser or a build that's spread over many processes shows up as a whole. Or grouped by the
+//! container, service or session each process belongs to, for processes that aren't related by
+//! parentage but still belong together.
+
+use std::sync::Mutex;
+
+use fxhash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use tauri::State;
+
+use crate::data_harvester::cgroups::CgroupOwner;
+use crate::data_harvester::processes::ProcessHarvest;
+use crate::{LatestData, Pid};
+
+/// Resource usage that adds up over a subtree.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Usage {
+    pub cpu_usage_percent: f64,
+    pub mem_usage_percent: f64,
+    pub mem_usage_bytes: u64,
+    pub read_bytes_per_sec: u64,
+    pub write_bytes_per_sec: u64,
+}
+
+impl Usage {
+    fn of(process: &ProcessHarvest) -> Self {
+        Usage {
+            cpu_usage_percent: process.cpu_usage_percent,
+            mem_usage_percent: process.mem_usage_percent,
+            mem_usage_bytes: process.mem_usage_bytes,
+            read_bytes_per_sec: process.read_bytes_per_sec,
+            write_bytes_per_sec: process.write_bytes_per_sec,
+        }
+    }
+
+    fn add(&mut self, other: &Usage) {
+        self.cpu_usage_percent += other.cpu_usage_percent;
+        self.mem_usage_percent += other.mem_usage_percent;
+        self.mem_usage_bytes += other.mem_usage_bytes;
+        self.read_bytes_per_sec += other.read_bytes_per_sec;
+        self.write_bytes_per_sec += other.write_bytes_per_sec;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessNode {
+    pub process: ProcessHarvest,
+    /// The process' own usage plus all its descendants'.
+    pub total: Usage,
+    pub descendants: usize,
+    /// A collapsed node has no `children`, though they still count towards `total`.
+    pub collapsed: bool,
+    /// Ordered by pid.
+    pub children: Vec<ProcessNode>,
+}
+
+struct TreeBuilder<'a> {
+    processes: &'a [ProcessHarvest],
+    /// Indices into `processes`, by the pid of the parent.
+    children: FxHashMap<Pid, Vec<usize>>,
+    collapsed: &'a FxHashSet<Pid>,
+    visited: Vec<bool>,
+}
+
+impl TreeBuilder<'_> {
+    fn node(&mut self, index: usize) -> ProcessNode {
+        self.visited[index] = true;
+        let process = &self.processes[index];
+        let mut node = ProcessNode {
+            process: process.clone(),
+            total: Usage::of(process),
+            descendants: 0,
+            collapsed: self.collapsed.contains(&process.pid),
+            children: Vec::new(),
+        };
+
+        let child_indices = self.children.remove(&process.pid).unwrap_or_default();
+        for child in child_indices {
+            // Only possible if the parents form a loop.
+            if self.visited[child] {
+                continue;
+            }
+            let child = self.node(child);
+            node.total.add(&child.total);
+            node.descendants += child.descendants + 1;
+            node.children.push(child);
+        }
+        if node.collapsed {
+            node.children.clear();
+        }
+        node
+    }
+}
+
+/// Arranges `processes` by their parents. Processes whose parent isn't in the list are at the top,
+/// ordered by pid.
+pub fn build(processes: &[ProcessHarvest], collapsed: &FxHashSet<Pid>) -> Vec<ProcessNode> {
+    let mut order: Vec<usize> = (0..processes.len()).collect();
+    order.sort_by_key(|&i| processes[i].pid);
+
+    let pids: FxHashSet<Pid> = processes.iter().map(|process| process.pid).collect();
+    let mut children: FxHashMap<Pid, Vec<usize>> = FxHashMap::default();
+    let mut roots = Vec::new();
+    for &i in &order {
+        let process = &processes[i];
+        match process.parent_pid {
+            Some(parent) if parent != process.pid && pids.contains(&parent) => {
+                children.entry(parent).or_default().push(i)
+            }
+            _ => roots.push(i),
+        }
+    }
+
+    let mut builder = TreeBuilder {
+        processes,
+        children,
+        collapsed,
+        visited: vec![false; processes.len()],
+    };
+    let mut tree: Vec<ProcessNode> = roots.into_iter().map(|i| builder.node(i)).collect();
+    // Pids get reused, so a process can look like the parent of its own ancestor. Whatever's in
+    // such a loop isn't under any of the roots, so each loop gets broken at its lowest pid instead.
+    for i in order {
+        if !builder.visited[i] {
+            tree.push(builder.node(i));
+        }
+    }
+    tree
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessGroup {
+    /// `None` for the processes that don't belong to anything, like kernel threads, or where
+    /// cgroups aren't available.
+    pub owner: Option<CgroupOwner>,
+    /// The usage of all the processes in the group.
+    pub total: Usage,
+    /// Ordered by pid.
+    pub pids: Vec<Pid>,
+}
+
+/// Groups `processes` by their owner. The groups are ordered by owner, with the processes that
+/// have none last.
+pub fn group(processes: &[ProcessHarvest]) -> Vec<ProcessGroup> {
+    let mut groups: FxHashMap<Option<&CgroupOwner>, ProcessGroup> = FxHashMap::default();
+    for process in processes {
+        let group = groups
+            .entry(process.owner.as_ref())
+            .or_insert_with(|| ProcessGroup {
+                owner: process.owner.clone(),
+                total: Usage::default(),
+                pids: Vec::new(),
+            });
+        group.total.add(&Usage::of(process));
+        group.pids.push(process.pid);
+    }
+
+    let mut groups: Vec<ProcessGroup> = groups.into_values().collect();
+    for group in &mut groups {
+        group.pids.sort_unstable();
+    }
+    groups.sort_by(|a, b| {
+        a.owner
+            .is_none()
+            .cmp(&b.owner.is_none())
+            .then_with(|| a.owner.cmp(&b.owner))
+    });
+    groups
+}
+
+/// The pids of the nodes the user has collapsed.
+#[derive(Default)]
+pub struct ProcessTreeState(Mutex<FxHashSet<Pid>>);
+
+/// Returns the latest process list as a tree.
+#[tauri::command]
+pub fn get_process_tree(
+    latest_data: State<LatestData>,
+    tree_state: State<ProcessTreeState>,
+) -> Vec<ProcessNode> {
+    let encoder = latest_data.0.lock().unwrap();
+    let Some(processes) = encoder
+        .latest()
+        .and_then(|data| data.list_of_processes.as_ref())
+    else {
+        return Vec::new();
+    };
+
+    let mut collapsed = tree_state.0.lock().unwrap();
+    // Forget about processes that are gone, so a new process that gets the same pid later doesn't
+    // start out collapsed.
+    collapsed.retain(|pid| processes.iter().any(|process| process.pid == *pid));
+    build(processes, &collapsed)
+}
+
+/// Returns the latest process list grouped by owner.
+#[tauri::command]
+pub fn get_process_groups(latest_data: State<LatestData>) -> Vec<ProcessGroup> {
+    let encoder = latest_data.0.lock().unwrap();
+    encoder
+        .latest()
+        .and_then(|data| data.list_of_processes.as_deref())
+        .map(group)
+        .unwrap_or_default()
+}
+
+/// Collapses or expands the node of the process `pid` in the trees from [`get_process_tree`].
+#[tauri::command]
+pub fn set_process_collapsed(tree_state: State<ProcessTreeState>, pid: Pid, collapsed: bool) {
+    let mut collapsed_pids = tree_state.0.lock().unwrap();
+    if collapsed {
+        collapsed_pids.insert(pid);
+    } else {
+        collapsed_pids.remove(&pid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(nodes: &[ProcessNode]) -> Vec<(Pid, f64, usize, Vec<Pid>)> {
+        nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.process.pid,
+                    node.total.cpu_usage_percent,
+                    node.descendants,
+                    node.children
+                        .iter()
+                        .map(|child| child.process.pid)
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_process_tree() {
+        // The pid, parent pid and CPU usage of each.
+        let processes: Vec<ProcessHarvest> = [
+            (300, 100, 5.0),
+            (1, 0, 0.0),
+            (100, 1, 1.0),
+            (200, 1, 2.0),
+            (301, 100, 5.0),
+            (400, 300, 10.0),
+            // Its parent has exited.
+            (500, 42, 0.5),
+            // A loop, from reused pids.
+            (600, 601, 1.0),
+            (601, 600, 1.0),
+        ]
+        .into_iter()
+        .map(|(pid, parent_pid, cpu_usage_percent)| ProcessHarvest {
+            parent_pid: Some(parent_pid),
+            cpu_usage_percent,
+            mem_usage_bytes: 1 << 20,
+            ..ProcessHarvest::for_test(pid, "test")
+        })
+        .collect();
+
+        let tree = build(&processes, &FxHashSet::default());
+        assert_eq!(
+            shape(&tree),
+            [
+                (1, 23.0, 5, vec![100, 200]),
+                (500, 0.5, 0, vec![]),
+                (600, 2.0, 1, vec![601]),
+            ]
+        );
+        assert_eq!(tree[0].total.mem_usage_bytes, 6 << 20);
+        assert_eq!(
+            shape(&tree[0].children[..1]),
+            [(100, 21.0, 3, vec![300, 301])]
+        );
+
+        let tree = build(&processes, &FxHashSet::from_iter([100]));
+        let browser = &tree[0].children[0];
+        assert!(browser.collapsed);
+        assert_eq!(shape(&tree[0].children[..1]), [(100, 21.0, 3, vec![])]);
+    }
+
+    #[test]
+    fn test_process_groups() {
+        let unit = |name: &str| {
+            Some(CgroupOwner::Unit {
+                name: name.to_string(),
+                user: None,
+            })
+        };
+        // The pid, CPU usage and owner of each.
+        let processes: Vec<ProcessHarvest> = [
+            (200, 2.0, unit("postgresql.service")),
+            (2, 0.5, None),
+            (300, 1.0, unit("nginx.service")),
+            (100, 3.0, unit("postgresql.service")),
+        ]
+        .into_iter()
+        .map(|(pid, cpu_usage_percent, owner)| ProcessHarvest {
+            cpu_usage_percent,
+            owner,
+            ..ProcessHarvest::for_test(pid, "test")
+        })
+        .collect();
+
+        let groups: Vec<_> = group(&processes)
+            .into_iter()
+            .map(|group| {
+                (
+                    group.owner.map(|owner| owner.to_string()),
+                    group.total.cpu_usage_percent,
+                    group.pids,
+                )
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Some("nginx.service".to_string()), 1.0, vec![300]),
+                (Some("postgresql.service".to_string()), 5.0, vec![100, 200]),
+                (None, 0.5, vec![2]),
+            ]
+        );
+    }
+}
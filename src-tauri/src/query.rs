@@ -0,0 +1,846 @@
+//! Filtering and sorting the process list, so the frontend can ask for just the processes it shows.
+//!
+//! A filter compares process fields with values, and combines the comparisons with `and`, `or`,
+//! `not` and parentheses:
+//!
+//! ```text
+//! cpu > 5 and (user = root or name =~ "^postgres")
+//! mem > 500MiB and not state = zombie
+//! elapsed < 5m and cputime > 30s
+//! ```
+//!
+//! Text is compared case-insensitively, whether with `=` and `!=` or with a regular expression
+//! through `=~` and `!~`. Values can be quoted, and need to be if they have spaces or parentheses.
+//! Sizes can have a unit, like `500MiB` or `2GB`. `mem` is a percentage unless it's compared with a
+//! size, in which case it's the bytes used. Times can have a unit too, from `ms` and `s` up to `m`,
+//! `h` and `d`, and are in seconds without one.
+
+use std::{borrow::Cow, cmp::Ordering, fmt};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::data_harvester::processes::ProcessHarvest;
+use crate::LatestData;
+
+/// Where in the filter something is, in characters, with the end exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Why a query couldn't be run. The span is where in the filter the problem is, if it's in there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryError {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(
+                f,
+                "{} (characters {} to {})",
+                self.message, span.start, span.end
+            ),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Pid,
+    ParentPid,
+    Cpu,
+    Mem,
+    MemBytes,
+    SharedMem,
+    VirtualMem,
+    Pss,
+    Uss,
+    Swap,
+    Read,
+    Write,
+    TotalRead,
+    TotalWrite,
+    #[cfg(target_family = "unix")]
+    Uid,
+    Name,
+    Command,
+    #[cfg(target_family = "unix")]
+    User,
+    State,
+    CpuTime,
+    Elapsed,
+    Nice,
+    Priority,
+    Threads,
+    Tty,
+    OpenFiles,
+    Cgroup,
+    Owner,
+}
+
+/// Every field a filter can name, by the name it's listed under in error messages. Other names
+/// for them are left to [`Field::from_name`].
+const FIELDS: &[(&str, Field)] = &[
+    ("pid", Field::Pid),
+    ("ppid", Field::ParentPid),
+    ("cpu", Field::Cpu),
+    ("mem", Field::Mem),
+    ("shared", Field::SharedMem),
+    ("virt", Field::VirtualMem),
+    ("pss", Field::Pss),
+    ("uss", Field::Uss),
+    ("swap", Field::Swap),
+    ("read", Field::Read),
+    ("write", Field::Write),
+    ("total_read", Field::TotalRead),
+    ("total_write", Field::TotalWrite),
+    #[cfg(target_family = "unix")]
+    ("uid", Field::Uid),
+    ("name", Field::Name),
+    ("command", Field::Command),
+    #[cfg(target_family = "unix")]
+    ("user", Field::User),
+    ("state", Field::State),
+    ("cputime", Field::CpuTime),
+    ("elapsed", Field::Elapsed),
+    ("nice", Field::Nice),
+    ("priority", Field::Priority),
+    ("threads", Field::Threads),
+    ("tty", Field::Tty),
+    ("fds", Field::OpenFiles),
+    ("cgroup", Field::Cgroup),
+    ("owner", Field::Owner),
+];
+
+/// For error messages.
+fn field_names() -> String {
+    let names: Vec<&str> = FIELDS.iter().map(|&(name, _)| name).collect();
+    let (last, rest) = names.split_last().expect("there are fields");
+    format!("{} or {}", rest.join(", "), last)
+}
+
+enum FieldValue<'a> {
+    Number(Option<f64>),
+    Text(Cow<'a, str>),
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        let name = name.to_ascii_lowercase();
+        let name = match name.as_str() {
+            "parent_pid" => "ppid",
+            "memory" => "mem",
+            "virtual" => "virt",
+            "cmd" => "command",
+            "cpu_time" | "time" => "cputime",
+            "age" => "elapsed",
+            "ni" => "nice",
+            "prio" => "priority",
+            "open_files" => "fds",
+            name => name,
+        };
+        FIELDS
+            .iter()
+            .find(|&&(field_name, _)| field_name == name)
+            .map(|&(_, field)| field)
+    }
+
+    fn is_text(self) -> bool {
+        match self {
+            Field::Name
+            | Field::Command
+            | Field::State
+            | Field::Tty
+            | Field::Cgroup
+            | Field::Owner => true,
+            #[cfg(target_family = "unix")]
+            Field::User => true,
+            _ => false,
+        }
+    }
+
+    /// Which units values compared with this field can have.
+    fn accepts(self, unit: Unit) -> bool {
+        matches!(
+            (self, unit),
+            (_, Unit::None)
+                | (Field::Cpu | Field::Mem, Unit::Percent)
+                | (
+                    Field::Mem
+                        | Field::MemBytes
+                        | Field::SharedMem
+                        | Field::VirtualMem
+                        | Field::Pss
+                        | Field::Uss
+                        | Field::Swap
+                        | Field::Read
+                        | Field::Write
+                        | Field::TotalRead
+                        | Field::TotalWrite,
+                    Unit::Bytes(_),
+                )
+                | (Field::CpuTime | Field::Elapsed, Unit::Seconds(_))
+        )
+    }
+
+    fn value(self, process: &ProcessHarvest) -> FieldValue<'_> {
+        let number = |value: f64| FieldValue::Number(Some(value));
+        match self {
+            Field::Pid => number(process.pid as f64),
+            Field::ParentPid => FieldValue::Number(process.parent_pid.map(|pid| pid as f64)),
+            Field::Cpu => number(process.cpu_usage_percent),
+            Field::Mem => number(process.mem_usage_percent),
+            Field::MemBytes => number(process.mem_usage_bytes as f64),
+            Field::SharedMem => {
+                FieldValue::Number(process.shared_mem_bytes.map(|bytes| bytes as f64))
+            }
+            Field::VirtualMem => number(process.virtual_mem_bytes as f64),
+            Field::Pss => FieldValue::Number(process.pss_bytes.map(|bytes| bytes as f64)),
+            Field::Uss => FieldValue::Number(process.uss_bytes.map(|bytes| bytes as f64)),
+            Field::Swap => FieldValue::Number(process.swap_bytes.map(|bytes| bytes as f64)),
+            Field::Read => number(process.read_bytes_per_sec as f64),
+            Field::Write => number(process.write_bytes_per_sec as f64),
+            Field::TotalRead => number(process.total_read_bytes as f64),
+            Field::TotalWrite => number(process.total_write_bytes as f64),
+            #[cfg(target_family = "unix")]
+            Field::Uid => FieldValue::Number(process.uid.map(f64::from)),
+            Field::Name => FieldValue::Text(Cow::Borrowed(&process.name)),
+            Field::Command => FieldValue::Text(Cow::Borrowed(&process.command)),
+            #[cfg(target_family = "unix")]
+            Field::User => FieldValue::Text(Cow::Borrowed(&process.user)),
+            Field::State => FieldValue::Text(Cow::Borrowed(&process.process_state.0)),
+            Field::CpuTime => {
+                FieldValue::Number(process.cpu_time_millis.map(|millis| millis as f64 / 1000.0))
+            }
+            Field::Elapsed => number(process.elapsed_millis as f64 / 1000.0),
+            Field::Nice => FieldValue::Number(process.nice.map(|nice| nice as f64)),
+            Field::Priority => FieldValue::Number(process.priority.map(|priority| priority as f64)),
+            Field::Threads => FieldValue::Number(process.thread_count.map(|count| count as f64)),
+            Field::Tty => FieldValue::Text(Cow::Borrowed(process.tty.as_deref().unwrap_or(""))),
+            Field::Cgroup => {
+                FieldValue::Text(Cow::Borrowed(process.cgroup.as_deref().unwrap_or("")))
+            }
+            Field::Owner => FieldValue::Text(
+                process
+                    .owner
+                    .as_ref()
+                    .map_or(Cow::Borrowed(""), |owner| Cow::Owned(owner.to_string())),
+            ),
+            Field::OpenFiles => FieldValue::Number(process.open_files.map(|count| count as f64)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches,
+    NotMatches,
+}
+
+impl Op {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Matches | Op::NotMatches => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    None,
+    Percent,
+    /// How many bytes one of the unit is.
+    Bytes(f64),
+    /// How many seconds one of the unit is.
+    Seconds(f64),
+}
+
+/// Splits a value like `500MiB` into the number and its unit.
+fn parse_number(value: &str) -> Option<(f64, Unit)> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    // Rates can be written either way, like `read > 1MiB` or `read > 1MiB/s`.
+    let unit = unit.strip_suffix("/s").unwrap_or(unit);
+    let unit = match unit.to_ascii_lowercase().as_str() {
+        "" => Unit::None,
+        "%" => Unit::Percent,
+        "b" => Unit::Bytes(1.0),
+        "kb" => Unit::Bytes(1e3),
+        "mb" => Unit::Bytes(1e6),
+        "gb" => Unit::Bytes(1e9),
+        "tb" => Unit::Bytes(1e12),
+        "kib" => Unit::Bytes(1024.0),
+        "mib" => Unit::Bytes(1024.0 * 1024.0),
+        "gib" => Unit::Bytes(1024.0 * 1024.0 * 1024.0),
+        "tib" => Unit::Bytes(1024.0 * 1024.0 * 1024.0 * 1024.0),
+        "ms" => Unit::Seconds(0.001),
+        "s" => Unit::Seconds(1.0),
+        "m" | "min" => Unit::Seconds(60.0),
+        "h" => Unit::Seconds(60.0 * 60.0),
+        "d" => Unit::Seconds(24.0 * 60.0 * 60.0),
+        _ => return None,
+    };
+    Some((number, unit))
+}
+
+#[derive(Debug)]
+enum Comparison {
+    Number { field: Field, op: Op, value: f64 },
+    Text { field: Field, op: Op, value: String },
+    Regex { field: Field, op: Op, regex: Regex },
+}
+
+impl Comparison {
+    fn matches(&self, process: &ProcessHarvest) -> bool {
+        match self {
+            Comparison::Number { field, op, value } => match field.value(process) {
+                FieldValue::Number(Some(actual)) => actual
+                    .partial_cmp(value)
+                    .map_or(false, |ordering| op.holds(ordering)),
+                _ => false,
+            },
+            Comparison::Text { field, op, value } => match field.value(process) {
+                FieldValue::Text(actual) => op.holds(actual.to_lowercase().cmp(value)),
+                FieldValue::Number(_) => false,
+            },
+            Comparison::Regex { field, op, regex } => match field.value(process) {
+                FieldValue::Text(actual) => regex.is_match(&actual) == (*op == Op::Matches),
+                FieldValue::Number(_) => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Comparison(Comparison),
+}
+
+impl Expr {
+    fn matches(&self, process: &ProcessHarvest) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(process) && right.matches(process),
+            Expr::Or(left, right) => left.matches(process) || right.matches(process),
+            Expr::Not(expr) => !expr.matches(process),
+            Expr::Comparison(comparison) => comparison.matches(process),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LeftParen,
+    RightParen,
+    Op(Op),
+    /// A bare word, which is a field name, a keyword or an unquoted value.
+    Word(String),
+    Quoted(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// In bytes, until it's turned into a [`Span`] for an error.
+    start: usize,
+    end: usize,
+}
+
+impl Token {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.kind, TokenKind::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            input,
+            tokens: Vec::new(),
+            next: 0,
+        };
+        parser.tokenize()?;
+        Ok(parser)
+    }
+
+    fn error(&self, message: impl Into<String>, start: usize, end: usize) -> QueryError {
+        let chars = |bytes: usize| self.input[..bytes].chars().count();
+        QueryError {
+            message: message.into(),
+            span: Some(Span {
+                start: chars(start),
+                end: chars(end),
+            }),
+        }
+    }
+
+    fn tokenize(&mut self) -> Result<(), QueryError> {
+        let mut chars = self.input.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let kind = match c {
+                c if c.is_whitespace() => continue,
+                '(' => TokenKind::LeftParen,
+                ')' => TokenKind::RightParen,
+                '=' | '!' | '<' | '>' => {
+                    let next = chars.peek().map(|(_, next)| *next);
+                    let (op, two_chars) = match (c, next) {
+                        ('=', Some('~')) => (Op::Matches, true),
+                        ('=', Some('=')) => (Op::Eq, true),
+                        ('=', _) => (Op::Eq, false),
+                        ('!', Some('~')) => (Op::NotMatches, true),
+                        ('!', Some('=')) => (Op::Ne, true),
+                        ('<', Some('=')) => (Op::Le, true),
+                        ('<', _) => (Op::Lt, false),
+                        ('>', Some('=')) => (Op::Ge, true),
+                        ('>', _) => (Op::Gt, false),
+                        _ => {
+                            return Err(self.error(
+                                "expected `!=` or `!~`; use `not` to negate",
+                                start,
+                                start + 1,
+                            ))
+                        }
+                    };
+                    if two_chars {
+                        chars.next();
+                    }
+                    TokenKind::Op(op)
+                }
+                '"' | '\'' => {
+                    let mut value = String::new();
+                    let mut closed = false;
+                    while let Some((_, next)) = chars.next() {
+                        match next {
+                            '\\' => {
+                                if let Some((_, escaped)) = chars.next() {
+                                    value.push(escaped);
+                                }
+                            }
+                            next if next == c => {
+                                closed = true;
+                                break;
+                            }
+                            next => value.push(next),
+                        }
+                    }
+                    if !closed {
+                        return Err(self.error("unclosed quote", start, self.input.len()));
+                    }
+                    TokenKind::Quoted(value)
+                }
+                _ => {
+                    let mut value = c.to_string();
+                    while let Some((_, next)) = chars.peek() {
+                        if next.is_whitespace() || "()=!<>\"'".contains(*next) {
+                            break;
+                        }
+                        value.push(*next);
+                        chars.next();
+                    }
+                    TokenKind::Word(value)
+                }
+            };
+            let end = chars.peek().map_or(self.input.len(), |(end, _)| *end);
+            self.tokens.push(Token { kind, start, end });
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    /// Where the next token is, or the end of the filter if there isn't one.
+    fn next_span(&self) -> (usize, usize) {
+        self.peek()
+            .map_or((self.input.len(), self.input.len()), |token| {
+                (token.start, token.end)
+            })
+    }
+
+    fn parse(mut self) -> Result<Expr, QueryError> {
+        let expr = self.parse_or()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(token) if token.kind == TokenKind::RightParen => {
+                Err(self.error("unmatched `)`", token.start, token.end))
+            }
+            Some(token) => Err(self.error(
+                "expected `and`, `or` or the end of the filter",
+                token.start,
+                token.end,
+            )),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.parse_and()?;
+        while self.peek().map_or(false, |token| token.is_keyword("or")) {
+            self.advance();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.parse_not()?;
+        while self.peek().map_or(false, |token| token.is_keyword("and")) {
+            self.advance();
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QueryError> {
+        if self.peek().map_or(false, |token| token.is_keyword("not")) {
+            self.advance();
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        let (start, end) = self.next_span();
+        match self.advance() {
+            Some(Token {
+                kind: TokenKind::LeftParen,
+                ..
+            }) => {
+                let expr = self.parse_or()?;
+                match self.advance() {
+                    Some(Token {
+                        kind: TokenKind::RightParen,
+                        ..
+                    }) => Ok(expr),
+                    _ => Err(self.error("unclosed `(`", start, end)),
+                }
+            }
+            Some(Token {
+                kind: TokenKind::Word(name),
+                ..
+            }) => {
+                let field = Field::from_name(&name).ok_or_else(|| {
+                    self.error(
+                        format!("unknown field `{}`; expected {}", name, field_names()),
+                        start,
+                        end,
+                    )
+                })?;
+                self.parse_comparison(field)
+            }
+            _ => Err(self.error("expected a comparison like `cpu > 5`", start, end)),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field) -> Result<Expr, QueryError> {
+        let (start, end) = self.next_span();
+        let op = match self.advance() {
+            Some(Token {
+                kind: TokenKind::Op(op),
+                ..
+            }) => op,
+            _ => {
+                return Err(self.error(
+                    "expected one of `=`, `!=`, `<`, `<=`, `>`, `>=`, `=~` or `!~`",
+                    start,
+                    end,
+                ))
+            }
+        };
+        let op_span = (start, end);
+
+        let (start, end) = self.next_span();
+        let value = match self.advance() {
+            Some(Token {
+                kind: TokenKind::Word(value) | TokenKind::Quoted(value),
+                ..
+            }) => value,
+            _ => return Err(self.error("expected a value", start, end)),
+        };
+
+        let comparison = match op {
+            Op::Matches | Op::NotMatches => {
+                if !field.is_text() {
+                    return Err(self.error(
+                        "only text can be matched with a regular expression",
+                        op_span.0,
+                        op_span.1,
+                    ));
+                }
+                let regex = RegexBuilder::new(&value)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| {
+                        self.error(format!("invalid regular expression: {}", err), start, end)
+                    })?;
+                Comparison::Regex { field, op, regex }
+            }
+            Op::Eq | Op::Ne if field.is_text() => Comparison::Text {
+                field,
+                op,
+                value: value.to_lowercase(),
+            },
+            _ if field.is_text() => {
+                return Err(self.error(
+                    "text can only be compared with `=`, `!=`, `=~` or `!~`",
+                    op_span.0,
+                    op_span.1,
+                ))
+            }
+            _ => {
+                let (number, unit) = parse_number(&value)
+                    .ok_or_else(|| self.error("expected a number", start, end))?;
+                if !field.accepts(unit) {
+                    return Err(self.error("this field can't have that unit", start, end));
+                }
+                let (field, value) = match unit {
+                    Unit::Bytes(size) if field == Field::Mem => (Field::MemBytes, number * size),
+                    Unit::Bytes(size) | Unit::Seconds(size) => (field, number * size),
+                    Unit::None | Unit::Percent => (field, number),
+                };
+                Comparison::Number { field, op, value }
+            }
+        };
+        Ok(Expr::Comparison(comparison))
+    }
+}
+
+/// A parsed filter, ready to run against processes.
+#[derive(Debug)]
+pub struct Filter(Expr);
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        Parser::new(input)?.parse().map(Filter)
+    }
+
+    pub fn matches(&self, process: &ProcessHarvest) -> bool {
+        self.0.matches(process)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Sorts `processes` by the field called `sort_by`, which is any of the fields a filter can use.
+pub fn sort(
+    processes: &mut [ProcessHarvest],
+    sort_by: &str,
+    order: SortOrder,
+) -> Result<(), QueryError> {
+    let field = Field::from_name(sort_by).ok_or_else(|| QueryError {
+        message: format!(
+            "can't sort by unknown field `{}`; expected {}",
+            sort_by,
+            field_names()
+        ),
+        span: None,
+    })?;
+    processes.sort_by(|a, b| {
+        let ordering = match (field.value(a), field.value(b)) {
+            (FieldValue::Number(Some(a)), FieldValue::Number(Some(b))) => {
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            }
+            // Processes without a value go last, whichever the order.
+            (FieldValue::Number(a), FieldValue::Number(b)) => return a.is_none().cmp(&b.is_none()),
+            (FieldValue::Text(a), FieldValue::Text(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+            _ => Ordering::Equal,
+        };
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+    Ok(())
+}
+
+/// Returns the processes in the latest snapshot that match `filter`, sorted by `sort_by` (in
+/// descending order unless `order` says otherwise), and only the first `limit` of them.
+#[tauri::command]
+pub fn query_processes(
+    latest_data: State<LatestData>,
+    filter: Option<String>,
+    sort_by: Option<String>,
+    order: Option<SortOrder>,
+    limit: Option<usize>,
+) -> Result<Vec<ProcessHarvest>, QueryError> {
+    let filter = filter
+        .filter(|filter| !filter.trim().is_empty())
+        .map(|filter| Filter::parse(&filter))
+        .transpose()?;
+
+    let mut processes: Vec<ProcessHarvest> = {
+        let encoder = latest_data.0.lock().unwrap();
+        let list = encoder
+            .latest()
+            .and_then(|data| data.list_of_processes.as_ref());
+        list.into_iter()
+            .flatten()
+            .filter(|process| {
+                filter
+                    .as_ref()
+                    .map_or(true, |filter| filter.matches(process))
+            })
+            .cloned()
+            .collect()
+    };
+    if let Some(sort_by) = sort_by {
+        sort(&mut processes, &sort_by, order.unwrap_or_default())?;
+    }
+    if let Some(limit) = limit {
+        processes.truncate(limit);
+    }
+    Ok(processes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pids(filter: &str) -> Vec<crate::Pid> {
+        // The pid, name, CPU usage and memory usage of each. Each has been running for a second for
+        // every unit of its pid, and ran for a minute for every percent of CPU usage.
+        let processes: Vec<ProcessHarvest> = [
+            (1, "systemd", 0.1, 12 << 20),
+            (200, "postgres", 8.0, 900 << 20),
+            (201, "postgres: writer", 0.5, 300 << 20),
+            (300, "firefox", 25.0, 2 << 30),
+            (400, "sshd", 6.0, 8 << 20),
+        ]
+        .into_iter()
+        .map(|(pid, name, cpu, mem): (_, _, f64, u64)| ProcessHarvest {
+            cpu_usage_percent: cpu,
+            mem_usage_bytes: mem,
+            mem_usage_percent: mem as f64 / (16u64 << 30) as f64 * 100.0,
+            elapsed_millis: pid as u64 * 1000,
+            cpu_time_millis: Some((cpu * 60_000.0) as u64),
+            ..ProcessHarvest::for_test(pid, name)
+        })
+        .collect();
+        #[cfg(target_family = "unix")]
+        let processes: Vec<ProcessHarvest> = processes
+            .into_iter()
+            .zip(["root", "postgres", "postgres", "alice", "root"])
+            .map(|(process, user)| ProcessHarvest {
+                user: user.into(),
+                ..process
+            })
+            .collect();
+        let filter = Filter::parse(filter).unwrap();
+        processes
+            .iter()
+            .filter(|process| filter.matches(process))
+            .map(|process| process.pid)
+            .collect()
+    }
+
+    #[test]
+    fn test_filter() {
+        #[cfg(target_family = "unix")]
+        assert_eq!(
+            pids(r#"cpu > 5 and (user = root or name =~ "^postgres")"#),
+            [200, 400]
+        );
+        assert_eq!(pids("mem > 500MiB"), [200, 300]);
+        assert_eq!(pids("mem >= 10%"), [300]);
+        assert_eq!(pids("not name = Firefox and cpu<1"), [1, 201]);
+        assert_eq!(pids("pid = 1 or pid = 300 and cpu > 50"), [1]);
+        assert_eq!(
+            pids("command !~ 'post' and state = sleeping"),
+            [1, 300, 400]
+        );
+        assert_eq!(pids("cputime > 5m"), [200, 300, 400]);
+        assert_eq!(pids("elapsed < 5m and cputime > 300"), [200]);
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        let span = |filter: &str| {
+            let err = Filter::parse(filter).unwrap_err();
+            let span = err.span.unwrap();
+            (span.start, span.end)
+        };
+        assert_eq!(span("cpu > 5 and colour = red"), (12, 18));
+        assert_eq!(span("cpu > five"), (6, 10));
+        assert_eq!(span("mem > 5 parsecs"), (8, 15));
+        assert_eq!(span("(cpu > 5 or mem > 5"), (0, 1));
+        assert_eq!(span("name =~ '('"), (8, 11));
+        assert_eq!(span("cpu =~ 5"), (4, 6));
+        assert_eq!(span("pid > 5MiB"), (6, 10));
+        assert_eq!(span("cpu > 5m"), (6, 8));
+        assert_eq!(span("name = 'unclosed"), (7, 16));
+        assert_eq!(span("cpu >"), (5, 5));
+        // In characters rather than bytes.
+        assert_eq!(span("name = 'café' oops"), (14, 18));
+    }
+
+    #[test]
+    fn test_field_names() {
+        assert_eq!(Field::from_name("CMD"), Some(Field::Command));
+        assert_eq!(Field::from_name("open_files"), Some(Field::OpenFiles));
+        assert_eq!(Field::from_name("colour"), None);
+        assert!(field_names().starts_with("pid, ppid, cpu, "));
+        assert!(field_names().ends_with(", cgroup or owner"));
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut processes: Vec<ProcessHarvest> = [(1, "b", 1.0), (2, "A", 3.0), (3, "c", 2.0)]
+            .into_iter()
+            .map(|(pid, name, cpu_usage_percent)| ProcessHarvest {
+                cpu_usage_percent,
+                ..ProcessHarvest::for_test(pid, name)
+            })
+            .collect();
+        sort(&mut processes, "cpu", SortOrder::Desc).unwrap();
+        let pids: Vec<_> = processes.iter().map(|process| process.pid).collect();
+        assert_eq!(pids, [2, 3, 1]);
+
+        sort(&mut processes, "name", SortOrder::Asc).unwrap();
+        let pids: Vec<_> = processes.iter().map(|process| process.pid).collect();
+        assert_eq!(pids, [2, 1, 3]);
+
+        assert!(sort(&mut processes, "colour", SortOrder::Asc).is_err());
+    }
+}
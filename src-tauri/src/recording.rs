@@ -0,0 +1,443 @@
+//! Recording sessions to a file and replaying them, so what happened on someone else's machine can
+//! be watched on your own.
+//!
+//! A recording is gzipped, newline-delimited JSON, with a snapshot per line along with when it was
+//! taken. A replay publishes the recorded snapshots the same way the collection thread publishes
+//! live ones, which are held back until the replay is stopped.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+
+use crate::data_harvester::{unix_time_millis, Data};
+use crate::utils::error::{self, ToeError};
+use crate::utils::logging::log_at;
+
+/// Replays can't be sped up or slowed down any more than this.
+const MAX_SPEED: f64 = 100.0;
+
+#[derive(Serialize)]
+struct FrameRef<'a> {
+    /// In milliseconds since the Unix epoch.
+    time: u64,
+    data: &'a Data,
+}
+
+#[derive(Deserialize)]
+struct Frame {
+    time: u64,
+    data: Data,
+}
+
+/// `~/.local/share/toerings/recordings/toerings-20230101-120000.ndjson.gz` on Linux, with the
+/// current time in UTC.
+fn default_recording_path() -> Option<PathBuf> {
+    let format = time::macros::format_description!("[year][month][day]-[hour][minute][second]");
+    let now = time::OffsetDateTime::now_utc().format(format).ok()?;
+    let dir = dirs::data_dir()?.join("toerings").join("recordings");
+    Some(dir.join(format!("toerings-{}.ndjson.gz", now)))
+}
+
+pub struct Recorder {
+    path: PathBuf,
+    writer: GzEncoder<BufWriter<File>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            writer: GzEncoder::new(file, Compression::default()),
+        })
+    }
+
+    pub fn record(&mut self, data: &Data) -> io::Result<()> {
+        let frame = FrameRef {
+            time: unix_time_millis(),
+            data,
+        };
+        serde_json::to_writer(&mut self.writer, &frame)?;
+        self.writer.write_all(b"\n")?;
+        // Whatever's being recorded might well crash the machine, so don't keep anything back.
+        self.writer.flush()
+    }
+
+    pub fn finish(self) -> io::Result<PathBuf> {
+        self.writer.finish()?.flush()?;
+        Ok(self.path)
+    }
+}
+
+/// Reads a whole recording. A recording that was cut short, say because the machine crashed, is
+/// read up to where it ends.
+fn read_recording(path: &Path) -> io::Result<Vec<Frame>> {
+    let reader = BufReader::new(GzDecoder::new(BufReader::new(File::open(path)?)));
+    let mut frames = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let frame = line.and_then(|line| {
+            serde_json::from_str(&line).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} on line {}", err, i + 1),
+                )
+            })
+        });
+        match frame {
+            Ok(frame) => frames.push(frame),
+            Err(err) if frames.is_empty() => return Err(err),
+            Err(err) => {
+                log_at!(
+                    warn,
+                    "Only read {} snapshots from {}: {}",
+                    frames.len(),
+                    path.display(),
+                    err
+                );
+                break;
+            }
+        }
+    }
+
+    if frames.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the recording is empty",
+        ));
+    }
+    Ok(frames)
+}
+
+/// The recording in progress, if there is one.
+#[derive(Default)]
+pub struct RecordingState(Mutex<Option<Recorder>>);
+
+impl RecordingState {
+    pub fn record(&self, data: &Data) {
+        let mut recorder = self.0.lock().unwrap();
+        if let Some(active) = recorder.as_mut() {
+            if let Err(err) = active.record(data) {
+                log_at!(
+                    error,
+                    "Couldn't record, so the recording was stopped: {}",
+                    err
+                );
+                *recorder = None;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReplayStatus {
+    pub path: PathBuf,
+    /// The index of the snapshot last shown.
+    pub position: usize,
+    /// How many snapshots there are.
+    pub length: usize,
+    /// When the snapshot last shown was recorded, in milliseconds since the Unix epoch.
+    pub time: u64,
+    pub paused: bool,
+    /// 2 is twice as fast as it was recorded.
+    pub speed: f64,
+}
+
+#[derive(Debug)]
+struct Playback {
+    /// The index of the next snapshot to show.
+    next: usize,
+    paused: bool,
+    /// Show the next snapshot even if paused, as the user just jumped to it.
+    seeked: bool,
+    speed: f64,
+    stopped: bool,
+    /// Counts changes to the above, so the replay thread knows to stop waiting.
+    changes: u64,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            next: 0,
+            paused: false,
+            seeked: false,
+            speed: 1.0,
+            stopped: false,
+            changes: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Control {
+    playback: Mutex<Playback>,
+    changed: Condvar,
+}
+
+struct Replay {
+    path: PathBuf,
+    times: Vec<u64>,
+    control: Arc<Control>,
+    thread: JoinHandle<()>,
+}
+
+impl Replay {
+    fn start(app_handle: AppHandle, path: &Path) -> error::Result<Self> {
+        let frames = read_recording(path)
+            .map_err(|err| ToeError::InvalidIo(format!("{}: {}", path.display(), err)))?;
+        let times: Vec<u64> = frames.iter().map(|frame| frame.time).collect();
+        let control = Arc::new(Control::default());
+
+        let thread = thread::Builder::new().name("replay".to_string()).spawn({
+            let path = path.to_path_buf();
+            let control = control.clone();
+            move || play(app_handle, &path, &frames, &control)
+        })?;
+
+        Ok(Replay {
+            path: path.to_path_buf(),
+            times,
+            control,
+            thread,
+        })
+    }
+
+    fn status(&self) -> ReplayStatus {
+        status(
+            &self.path,
+            &self.times,
+            &self.control.playback.lock().unwrap(),
+        )
+    }
+
+    fn update(&self, update: impl FnOnce(&mut Playback)) {
+        let mut playback = self.control.playback.lock().unwrap();
+        update(&mut playback);
+        playback.changes += 1;
+        self.control.changed.notify_all();
+    }
+
+    fn stop(self) {
+        self.update(|playback| playback.stopped = true);
+        let _ = self.thread.join();
+    }
+}
+
+fn status(path: &Path, times: &[u64], playback: &Playback) -> ReplayStatus {
+    let position = playback.next.saturating_sub(1).min(times.len() - 1);
+    ReplayStatus {
+        path: path.to_path_buf(),
+        position,
+        length: times.len(),
+        time: times[position],
+        paused: playback.paused,
+        speed: playback.speed,
+    }
+}
+
+/// Runs on the replay thread, publishing each snapshot as far apart as they were recorded.
+fn play(app_handle: AppHandle, path: &Path, frames: &[Frame], control: &Control) {
+    let times: Vec<u64> = frames.iter().map(|frame| frame.time).collect();
+    let mut playback = control.playback.lock().unwrap();
+    loop {
+        if playback.stopped {
+            return;
+        }
+        if playback.paused && !playback.seeked {
+            playback = control.changed.wait(playback).unwrap();
+            continue;
+        }
+
+        if playback.next == frames.len() {
+            // Resumed after reaching the end, so start over.
+            playback.next = 0;
+        }
+        let frame = &frames[playback.next];
+        let mut data = frame.data.clone();
+        // So it looks like it was just collected.
+        data.last_collection_time = Instant::now();
+        crate::publish(&app_handle, &data);
+
+        playback.next += 1;
+        playback.seeked = false;
+        if playback.next == frames.len() {
+            playback.paused = true;
+        }
+        let _ = app_handle.emit_all("replayStatus", status(path, &times, &playback));
+
+        let delay = match frames.get(playback.next) {
+            Some(next_frame) => {
+                let recorded = next_frame.time.saturating_sub(frame.time);
+                Duration::from_millis(recorded).div_f64(playback.speed)
+            }
+            None => Duration::ZERO,
+        };
+        let changes = playback.changes;
+        playback = control
+            .changed
+            .wait_timeout_while(playback, delay, |playback| playback.changes == changes)
+            .unwrap()
+            .0;
+    }
+}
+
+/// The replay in progress, if there is one. Live snapshots aren't published while there is.
+#[derive(Default)]
+pub struct ReplayState(Mutex<Option<Replay>>);
+
+impl ReplayState {
+    pub fn is_active(&self) -> bool {
+        self.0.lock().unwrap().is_some()
+    }
+}
+
+/// Starts recording to `path`, or to a new file in the data directory. Returns where it's recording
+/// to.
+#[tauri::command]
+pub fn start_recording(
+    recording: State<RecordingState>,
+    path: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let path = path
+        .or_else(default_recording_path)
+        .ok_or("couldn't find a directory to save the recording in")?;
+    let recorder = Recorder::create(&path)
+        .map_err(|err| format!("couldn't create {}: {}", path.display(), err))?;
+
+    let mut active = recording.0.lock().unwrap();
+    if let Some(previous) = active.take() {
+        let _ = previous.finish();
+    }
+    *active = Some(recorder);
+    Ok(path)
+}
+
+/// Stops recording, returning where the recording was saved.
+#[tauri::command]
+pub fn stop_recording(recording: State<RecordingState>) -> Result<Option<PathBuf>, String> {
+    let recorder = recording.0.lock().unwrap().take();
+    recorder
+        .map(Recorder::finish)
+        .transpose()
+        .map_err(|err| err.to_string())
+}
+
+/// Where the recording in progress is being saved, if there is one.
+#[tauri::command]
+pub fn get_recording_path(recording: State<RecordingState>) -> Option<PathBuf> {
+    let recorder = recording.0.lock().unwrap();
+    recorder.as_ref().map(|recorder| recorder.path.clone())
+}
+
+/// Stops any replay in progress and starts replaying the recording at `path`.
+#[tauri::command]
+pub fn start_replay(
+    replay: State<ReplayState>,
+    app_handle: AppHandle,
+    path: PathBuf,
+) -> Result<ReplayStatus, String> {
+    let mut active = replay.0.lock().unwrap();
+    if let Some(previous) = active.take() {
+        previous.stop();
+    }
+    let started = Replay::start(app_handle, &path).map_err(|err| err.to_string())?;
+    let status = started.status();
+    *active = Some(started);
+    Ok(status)
+}
+
+/// Pauses or resumes the replay, changes its speed, or jumps to the snapshot at `position`.
+#[tauri::command]
+pub fn control_replay(
+    replay: State<ReplayState>,
+    paused: Option<bool>,
+    speed: Option<f64>,
+    position: Option<usize>,
+) -> Result<ReplayStatus, String> {
+    let active = replay.0.lock().unwrap();
+    let active = active.as_ref().ok_or("nothing is being replayed")?;
+    if let Some(speed) = speed {
+        if !(speed > 0.0 && speed <= MAX_SPEED) {
+            return Err(format!(
+                "the speed must be above 0 and at most {}",
+                MAX_SPEED
+            ));
+        }
+    }
+    if let Some(position) = position {
+        if position >= active.times.len() {
+            return Err(format!("there are only {} snapshots", active.times.len()));
+        }
+    }
+
+    active.update(|playback| {
+        if let Some(paused) = paused {
+            playback.paused = paused;
+        }
+        if let Some(speed) = speed {
+            playback.speed = speed;
+        }
+        if let Some(position) = position {
+            playback.next = position;
+            playback.seeked = true;
+        }
+    });
+    Ok(active.status())
+}
+
+/// Stops the replay, going back to live snapshots.
+#[tauri::command]
+pub fn stop_replay(replay: State<ReplayState>, app_handle: AppHandle) {
+    if let Some(active) = replay.0.lock().unwrap().take() {
+        active.stop();
+        let _ = app_handle.emit_all("replayStatus", None::<ReplayStatus>);
+    }
+}
+
+#[tauri::command]
+pub fn get_replay_status(replay: State<ReplayState>) -> Option<ReplayStatus> {
+    replay.0.lock().unwrap().as_ref().map(Replay::status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_cut_short_recording() {
+        let path = std::env::temp_dir().join(format!("toerings-{}.ndjson.gz", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+        for uptime in 1..=3 {
+            let data = Data {
+                uptime: Duration::from_secs(uptime),
+                ..Data::default()
+            };
+            recorder.record(&data).unwrap();
+        }
+        // Like a crash would, leave the gzip stream without its end.
+        std::mem::forget(recorder);
+
+        let frames = read_recording(&path).unwrap();
+        let uptimes: Vec<_> = frames.iter().map(|frame| frame.data.uptime).collect();
+        assert_eq!(
+            uptimes,
+            [1, 2, 3].map(Duration::from_secs).to_vec(),
+            "every flushed snapshot should be read back"
+        );
+        assert!(frames.windows(2).all(|pair| pair[0].time <= pair[1].time));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 use std::{borrow::Cow, result};
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[cfg(target_os = "linux")]
@@ -9,12 +10,14 @@ use procfs::ProcError;
 pub type Result<T> = result::Result<T, ToeError>;
 
 /// An error that can occur while Bottom runs.
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message")]
 pub enum ToeError {
     /// An error when there is an IO exception.
     #[error("IO exception, {0}")]
     InvalidIo(String),
     /// An error when the heim library encounters a problem.
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
     #[error("Error caused by Heim, {0}")]
     InvalidHeim(String),
     /// An error when the Crossterm library encounters a problem.
@@ -35,6 +38,12 @@ pub enum ToeError {
     /// An error to represent errors with querying.
     #[error("Query error, {0}")]
     QueryError(Cow<'static, str>),
+    /// An error when something isn't allowed, e.g. signalling another user's process.
+    #[error("Permission denied, {0}")]
+    PermissionDenied(String),
+    /// An error when a process can't be acted on, e.g. because it's exited.
+    #[error("Process error, {0}")]
+    ProcessError(String),
     /// An error that just signifies something minor went wrong; no message.
     #[error("Minor error.")]
     MinorError,
@@ -44,13 +53,19 @@ pub enum ToeError {
     ProcfsError(String),
 }
 
+impl From<anyhow::Error> for ToeError {
+    fn from(err: anyhow::Error) -> Self {
+        ToeError::GenericError(err.to_string())
+    }
+}
+
 impl From<std::io::Error> for ToeError {
     fn from(err: std::io::Error) -> Self {
         ToeError::InvalidIo(err.to_string())
     }
 }
 
-#[cfg(not(target_os = "freebsd"))]
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 impl From<heim::Error> for ToeError {
     fn from(err: heim::Error) -> Self {
         ToeError::InvalidHeim(err.to_string())
@@ -76,6 +91,18 @@ impl From<fern::InitError> for ToeError {
     }
 }
 
+impl From<toml::ser::Error> for ToeError {
+    fn from(err: toml::ser::Error) -> Self {
+        ToeError::ConfigError(err.to_string())
+    }
+}
+
+impl From<notify::Error> for ToeError {
+    fn from(err: notify::Error) -> Self {
+        ToeError::GenericError(err.to_string())
+    }
+}
+
 impl From<std::str::Utf8Error> for ToeError {
     fn from(err: std::str::Utf8Error) -> Self {
         ToeError::ConversionError(err.to_string())
@@ -1,8 +1,207 @@
+//! The log file, `~/.local/state/toerings/toerings.log` on Linux.
+//!
+//! The level comes from the `logging` section of the config file, unless the `TOERINGS_LOG`
+//! environment variable is set. Once the file grows past [`MAX_FILE_SIZE`], it's moved aside to
+//! `toerings.log.1` and a new one is started.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+#[cfg(feature = "fern")]
+use std::{fs::File, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+/// Logs with the `log` crate's macro for `$level`, e.g. `log_at!(warn, "Bad {}", thing)`. Without
+/// the `log` feature, the arguments are still used, so that nothing needs a `cfg` of its own just
+/// for logging.
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::$level!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+pub(crate) use log_at;
+
+/// How big the log file gets before it's rotated.
+#[cfg(feature = "fern")]
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// How many rotated files are kept around, besides the current one.
+#[cfg(feature = "fern")]
+const KEPT_FILES: usize = 2;
+
+/// Overrides the level from the config file, e.g. `TOERINGS_LOG=debug`.
+#[cfg(feature = "log")]
+pub const LEVEL_VAR: &str = "TOERINGS_LOG";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Error,
+    #[default]
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            "trace" => Ok(LogLevel::Trace),
+            _ => Err(format!("unknown log level {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Off => "off",
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => log::LevelFilter::Off,
+            LogLevel::Error => log::LevelFilter::Error,
+            LogLevel::Warn => log::LevelFilter::Warn,
+            LogLevel::Info => log::LevelFilter::Info,
+            LogLevel::Debug => log::LevelFilter::Debug,
+            LogLevel::Trace => log::LevelFilter::Trace,
+        }
+    }
+}
+
+/// The level set through [`LEVEL_VAR`], if it's set to one.
+#[cfg(feature = "log")]
+pub fn env_level() -> Option<LogLevel> {
+    let value = std::env::var(LEVEL_VAR).ok()?;
+    match value.parse() {
+        Ok(level) => Some(level),
+        Err(err) => {
+            log::warn!("Ignoring {}: {}", LEVEL_VAR, err);
+            None
+        }
+    }
+}
+
+/// Changes the level while the app is running. The environment variable still wins over the
+/// config file.
+pub fn set_level(config_level: LogLevel) {
+    #[cfg(feature = "log")]
+    log::set_max_level(env_level().unwrap_or(config_level).into());
+    #[cfg(not(feature = "log"))]
+    let _ = config_level;
+}
+
+/// `~/.local/state/toerings/toerings.log` on Linux. Other platforms don't have a state directory,
+/// so it goes with the local app data there.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("toerings").join("toerings.log"))
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Appends to a file, moving it aside once it's too big.
+#[cfg(feature = "fern")]
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+#[cfg(feature = "fern")]
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::options().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size,
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..KEPT_FILES).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
 #[cfg(feature = "fern")]
-pub fn init_logger(
-    min_level: log::LevelFilter,
-    debug_file_name: &std::ffi::OsStr,
-) -> Result<(), fern::InitError> {
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Sends everything that's logged to the file at `path`. Only messages at `min_level` or above
+/// are written, which [`set_level`] can change later.
+#[cfg(feature = "fern")]
+pub fn init_logger(min_level: LogLevel, path: &Path) -> Result<(), fern::InitError> {
+    let file: Box<dyn Write + Send> = Box::new(RotatingFile::open(path, MAX_FILE_SIZE)?);
+    init_output(min_level, file.into())
+}
+
+/// Sends everything that's logged to stderr, for when the log file can't be written.
+#[cfg(feature = "fern")]
+pub fn init_stderr_logger(min_level: LogLevel) -> Result<(), fern::InitError> {
+    init_output(min_level, io::stderr().into())
+}
+
+#[cfg(feature = "fern")]
+fn init_output(min_level: LogLevel, output: fern::Output) -> Result<(), fern::InitError> {
     fern::Dispatch::new()
         .format(|out, message, record| {
             // Note we aren't using local time since it only works on single-threaded processes.
@@ -23,9 +222,84 @@ pub fn init_logger(
                 message
             ))
         })
-        .level(min_level)
-        .chain(fern::log_file(debug_file_name)?)
+        .chain(output)
         .apply()?;
+    // The dispatch lets everything through, so that the level can be changed without setting it
+    // up again.
+    set_level(min_level);
 
     Ok(())
 }
+
+/// The last `lines` lines of the log at `path`, reaching back into the rotated file if the current
+/// one is shorter than that.
+pub fn read_tail(path: &Path, lines: usize) -> io::Result<String> {
+    let mut tail: Vec<String> = Vec::new();
+    for file_path in [path.to_path_buf(), rotated_path(path, 1)] {
+        let contents = match fs::read_to_string(&file_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        let wanted = lines - tail.len();
+        let mut older: Vec<String> = contents
+            .lines()
+            .rev()
+            .take(wanted)
+            .map(String::from)
+            .collect();
+        older.reverse();
+        older.append(&mut tail);
+        tail = older;
+        if tail.len() == lines {
+            break;
+        }
+    }
+
+    let mut tail = tail.join("\n");
+    if !tail.is_empty() {
+        tail.push('\n');
+    }
+    Ok(tail)
+}
+
+/// Returns the end of the log, for attaching to bug reports. Defaults to the last 500 lines.
+#[tauri::command]
+pub fn get_log_tail(lines: Option<usize>) -> Result<String, String> {
+    let path = default_path().ok_or("couldn't find a directory for the log file")?;
+    read_tail(&path, lines.unwrap_or(500)).map_err(|err| err.to_string())
+}
+
+#[cfg(all(test, feature = "fern"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_and_tail() {
+        let dir = std::env::temp_dir().join(format!("toerings-log-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("toerings.log");
+
+        let mut file = RotatingFile::open(&path, 20).unwrap();
+        for i in 0..8 {
+            // 10 bytes each, so every file holds two lines.
+            writeln!(file, "line {:04}", i).unwrap();
+        }
+        drop(file);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line 0006\nline 0007\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "line 0002\nline 0003\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+
+        assert_eq!(
+            read_tail(&path, 3).unwrap(),
+            "line 0005\nline 0006\nline 0007\n"
+        );
+        assert_eq!(read_tail(&path, 1).unwrap(), "line 0007\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,10 @@
-import { writable } from "svelte/store"
+import { writable, get } from "svelte/store"
+import type { Writable } from "svelte/store"
+import { invoke } from "@tauri-apps/api/tauri"
+import { listen } from "@tauri-apps/api/event"
 import { colord } from "colord"
+import type { Colord } from "colord"
+import { debounce, isEqual, omit } from "lodash-es"
 
 export const foregroundColor = writable(colord("#ffffff"))
 export const backgroundColor = writable(colord("rgba(0, 0, 0, 0.5)"))
@@ -8,3 +13,102 @@ export const accentColor = writable(colord("#ff00ff"))
 export const arcTrackColor = writable(colord("rgba(255, 255, 255, 0.2)"))
 export const arcCapColor = writable(colord("#ffffff"))
 export const fontFamily = writable("Inter, Avenir, Helvetica, Arial, sans-serif")
+// Why the config file couldn't be loaded, if it couldn't.
+export const configError = writable<ConfigFileError | null>(null)
+
+const colorStores: Record<string, Writable<Colord>> = {
+  foreground_color: foregroundColor,
+  background_color: backgroundColor,
+  title_color: titleColor,
+  accent_color: accentColor,
+  arc_track_color: arcTrackColor,
+  arc_cap_color: arcCapColor
+}
+
+// The config as last loaded from or saved to the config file.
+let config: Config | null = null
+
+function currentAppearance(): AppearanceConfig {
+  const appearance = { font_family: get(fontFamily) }
+  for (const [key, store] of Object.entries(colorStores)) {
+    appearance[key] = get(store).toHex()
+  }
+  return appearance as AppearanceConfig
+}
+
+// The same colors can be written many ways, e.g. "rgba(0, 0, 0, 0.5)" and "#00000080".
+function normalizeAppearance(appearance: AppearanceConfig): AppearanceConfig {
+  const normalized = { ...appearance }
+  for (const key of Object.keys(colorStores)) {
+    normalized[key] = colord(appearance[key]).toHex()
+  }
+  return normalized
+}
+
+// The color pickers fire on every little drag, so don't write the file for each one.
+const saveConfig = debounce(() => {
+  const appearance = currentAppearance()
+  if (config === null || isEqual(appearance, normalizeAppearance(config.appearance))) {
+    return
+  }
+  const newConfig = { ...config, appearance }
+  invoke("set_config", { config: newConfig })
+    .then(() => (config = newConfig))
+    .catch(err => console.error(`Couldn't save the config: ${err}`))
+}, 500)
+
+function applyConfig(newConfig: Config) {
+  // Set this first, so the changes below aren't saved right back.
+  config = newConfig
+  configError.set(null)
+  for (const [key, store] of Object.entries(colorStores)) {
+    store.set(colord(config.appearance[key]))
+  }
+  fontFamily.set(config.appearance.font_family)
+}
+
+// Sets the stores from the config file, and saves any later changes back to it. If the config file
+// can't be loaded the defaults are kept, and nothing is saved so as not to clobber the file. Edits
+// made to the file while the app is running are picked up too.
+export async function loadConfig() {
+  listen<ConfigChanged>("configChanged", event => {
+    if ("config" in event.payload) {
+      applyConfig(event.payload.config)
+    } else {
+      configError.set(event.payload.error)
+    }
+  })
+
+  try {
+    applyConfig(await invoke<Config>("get_config"))
+  } catch (err) {
+    configError.set(err)
+  }
+
+  for (const store of [...Object.values(colorStores), fontFamily]) {
+    store.subscribe(saveConfig)
+  }
+}
+
+// The replay being shown instead of live data, if there is one.
+export const replayStatus = writable<ReplayStatus | null>(null)
+
+export async function watchReplay() {
+  listen<ReplayStatus | null>("replayStatus", event => replayStatus.set(event.payload))
+  replayStatus.set(await invoke<ReplayStatus | null>("get_replay_status"))
+}
+
+// The alerts that are firing right now, by rule name.
+export const firingAlerts = writable<Record<string, AlertEvent>>({})
+
+function applyAlertEvent(event: AlertEvent) {
+  firingAlerts.update(firing =>
+    event.kind === "firing" ? { ...firing, [event.rule]: event } : omit(firing, event.rule)
+  )
+}
+
+export async function watchAlerts() {
+  listen<AlertEvent>("alert", event => applyAlertEvent(event.payload))
+  const history = await invoke<Array<AlertEvent>>("get_alert_history")
+  history.forEach(applyAlertEvent)
+}
@@ -0,0 +1,39 @@
+import { describe, expect, it } from "vitest"
+
+import { applyUpdate } from "./utils"
+
+function process(pid: string, cpu_usage_percent = 0): Process {
+  return { pid, name: `process ${pid}`, cpu_usage_percent } as Process
+}
+
+describe("applyUpdate", () => {
+  it("keeps the processes sorted by pid", () => {
+    const latest = {
+      hostname: "laptop",
+      list_of_processes: [process("1"), process("100")]
+    } as Data
+    const data = applyUpdate(latest, {
+      type: "delta",
+      seq: 2,
+      changed: {},
+      processes: {
+        added: [process("50"), process("200")],
+        removed: ["1"],
+        changed: [{ pid: "100", cpu_usage_percent: 12.5 }]
+      }
+    })
+    expect(data.list_of_processes.map(process => process.pid)).toEqual(["50", "100", "200"])
+    expect(data.list_of_processes[1].cpu_usage_percent).toBe(12.5)
+    expect(data.hostname).toBe("laptop")
+  })
+
+  it("needs a snapshot to apply a delta to", () => {
+    const data = applyUpdate(null, {
+      type: "delta",
+      seq: 2,
+      changed: {},
+      processes: { added: [], removed: [], changed: [] }
+    })
+    expect(data).toBeNull()
+  })
+})
@@ -43,3 +43,49 @@ export function saturatedPush<T>(arr: Array<T>, element: T, limit: number) {
     arr.shift()
   }
 }
+
+// Sums the series of a history query point by point, keeping the last `limit` sums. The result is
+// padded at the front with zeros, so it has exactly `limit` elements.
+export function historyValues(history: HistoryQuery, limit: number, scale = 1): Array<number> {
+  const sums = new Map<number, number>()
+  for (const series of history.series) {
+    for (const { time, value } of series.points) {
+      sums.set(time, (sums.get(time) ?? 0) + value * scale)
+    }
+  }
+  const values = [...sums.keys()]
+    .sort((a, b) => a - b)
+    .slice(-limit)
+    .map(time => sums.get(time))
+  return [...Array(limit - values.length).fill(0), ...values]
+}
+
+// Applies an update from the `dataCollected` event to the last snapshot. Returns `null` if an
+// update was missed in between, in which case the full snapshot has to be fetched again.
+export function applyUpdate(latest: Data | null, update: DataUpdate): Data | null {
+  if (update.type === "full") {
+    return update.data
+  }
+  if (!latest) {
+    return null
+  }
+
+  const data = { ...latest, ...update.changed }
+  if (data.list_of_processes && !("list_of_processes" in update.changed)) {
+    const { added, removed, changed } = update.processes
+    const removedPids = new Set(removed)
+    const changedByPid = new Map(changed.map(process => [process.pid, process]))
+    data.list_of_processes = data.list_of_processes
+      .filter(process => !removedPids.has(process.pid))
+      .map(process => {
+        const changes = changedByPid.get(process.pid)
+        return changes ? { ...process, ...changes } : process
+      })
+      .concat(added)
+    // The backend sends the list sorted by pid, and the processes table relies on that.
+    if (added.length) {
+      data.list_of_processes.sort((a, b) => Number(a.pid) - Number(b.pid))
+    }
+  }
+  return data
+}
@@ -11,9 +11,27 @@ interface Process {
   command: string
   pid: string
   parent_pid: string | null
+  // In milliseconds since the epoch.
+  start_time: number
+  // How long it had been running when it was read, in milliseconds.
+  elapsed_millis: number
+  cpu_time_millis: number | null
+  nice: number | null
+  priority: number | null
+  thread_count: number | null
+  threads: ProcessThread[] | null
+  tty: string | null
+  open_files: number | null
+  cgroup: string | null
+  owner: CgroupOwner | null
   cpu_usage_percent: number
   mem_usage_percent: number
   mem_usage_bytes: number
+  shared_mem_bytes: number | null
+  pss_bytes: number | null
+  uss_bytes: number | null
+  swap_bytes: number | null
+  virtual_mem_bytes: number
   read_bytes_per_sec: number
   write_bytes_per_sec: number
   total_read_bytes: number
@@ -23,6 +41,19 @@ interface Process {
   user: string | null
 }
 
+type CgroupOwner =
+  | { kind: "container"; runtime: "docker" | "podman" | "containerd" | "cri-o"; id: string }
+  | { kind: "unit"; name: string; user: number | null }
+  | { kind: "session"; uid: number; id: string }
+  | { kind: "slice"; name: string }
+
+interface ProcessThread {
+  tid: string
+  name: string
+  state: [string, string]
+  cpu_usage_percent: number
+}
+
 interface DiskData {
   name: string
   mount_point: string
@@ -65,11 +96,42 @@ interface SummaryData {
   os_version: string | null
 }
 
+// A container or systemd slice, from its cgroup. Linux only.
+interface ContainerData {
+  cgroup: string
+  owner: CgroupOwner
+  name: string
+  cpu_usage_percent: number
+  cpu_periods: number | null
+  throttled_periods: number | null
+  throttled_millis: number | null
+  mem_usage_bytes: number | null
+  // `null` without a limit.
+  mem_limit_bytes: number | null
+  read_bytes_per_sec: number
+  write_bytes_per_sec: number
+  total_read_bytes: number
+  total_write_bytes: number
+  pids: number | null
+}
+
 interface IOData {
   read_bytes: number
   write_bytes: number
 }
 
+type Subsystem =
+  | "cpu"
+  | "processes"
+  | "temperature"
+  | "network"
+  | "memory"
+  | "disks"
+  | "io"
+  | "batteries"
+  | "host"
+  | "containers"
+
 interface Data {
   last_collection_time: number
   uptime: string
@@ -77,17 +139,221 @@ interface Data {
   kernel_name: string | null
   kernel_version: string | null
   os_version: string | null
-  list_of_processes: Array<Process>
-  cpu: Array<CPUData>
-  load_avg: Array<number>
-  memory: MemData
-  swap: MemData
-  disks: Array<DiskData>
-  io: Record<string, IOData>
+  list_of_processes: Array<Process> | null
+  cpu: Array<CPUData> | null
+  load_avg: Array<number> | null
+  memory: MemData | null
+  swap: MemData | null
+  disks: Array<DiskData> | null
+  io: Record<string, IOData> | null
   local_ip: string | null
-  network: NetData
-  temperature_sensors: Array<TempData>
-  list_of_batteries?: Array<BatteryData>
-  arc?: MemData
-  gpu?: Array<[string, MemData]>
+  network: NetData | null
+  temperature_sensors: Array<TempData> | null
+  list_of_batteries?: Array<BatteryData> | null
+  arc?: MemData | null
+  gpu?: Array<[string, MemData]> | null
+  containers?: Array<ContainerData> | null
+  refreshed_at: Partial<Record<Subsystem, number>>
+  collector_status: Partial<Record<Subsystem, CollectorStatus>>
+}
+
+// The payload of the `dataCollected` event. After the first one, only what changed is sent.
+type DataUpdate =
+  | { type: "full"; seq: number; data: Data }
+  | {
+      type: "delta"
+      seq: number
+      changed: Partial<Data>
+      processes: {
+        added: Array<Process>
+        removed: Array<Process["pid"]>
+        changed: Array<Partial<Process> & Pick<Process, "pid">>
+      }
+    }
+
+interface ProcessUsage {
+  cpu_usage_percent: number
+  mem_usage_percent: number
+  mem_usage_bytes: number
+  read_bytes_per_sec: number
+  write_bytes_per_sec: number
+}
+
+// A node from `get_process_tree`. `total` is the process' own usage plus all its descendants'.
+interface ProcessNode {
+  process: Process
+  total: ProcessUsage
+  descendants: number
+  collapsed: boolean
+  children: Array<ProcessNode>
+}
+
+// A group from `get_process_groups`. Processes without an owner, like kernel threads, are grouped
+// under `null`.
+interface ProcessGroup {
+  owner: CgroupOwner | null
+  total: ProcessUsage
+  pids: string[]
+}
+
+// A section of `ProcessDetails` that may not have been readable, usually because the process
+// belongs to another user, in which case the error's kind is "PermissionDenied".
+type DetailSection<T> = { Ok: T } | { Err: { kind: string; message?: string } }
+
+// From `get_process_details`. Linux only.
+interface ProcessDetails {
+  pid: string
+  name: string
+  start_time: number
+  exe: DetailSection<string>
+  cwd: DetailSection<string>
+  argv: DetailSection<string[]>
+  environ: DetailSection<Record<string, string>>
+  limits: DetailSection<
+    Array<{ name: string; soft: number | null; hard: number | null; unit: string | null }>
+  >
+  open_files: DetailSection<
+    Array<{ fd: number; kind: "file" | "socket" | "pipe" | "anon_inode" | "other"; target: string }>
+  >
+  memory: DetailSection<MemoryRollup>
+  cgroup: DetailSection<{ path: string; owner: CgroupOwner | null } | null>
+  // The inode of each namespace, by type.
+  namespaces: DetailSection<Record<string, number>>
+  // The parent first, up to init.
+  parents: DetailSection<Array<{ pid: string; name: string }>>
+}
+
+// In bytes, from `/proc/<pid>/smaps_rollup`.
+interface MemoryRollup {
+  rss_bytes: number
+  pss_bytes: number
+  uss_bytes: number
+  swap_bytes: number
+}
+
+// What `query_processes` rejects. The span is in characters, with the end exclusive.
+interface QueryError {
+  message: string
+  span: { start: number; end: number } | null
+}
+
+// When it last succeeded is in `Data.refreshed_at`.
+interface CollectorStatus {
+  last_error: { kind: string; message?: string } | null
+  last_duration: string
+}
+
+interface AppearanceConfig {
+  foreground_color: string
+  background_color: string
+  title_color: string
+  accent_color: string
+  arc_track_color: string
+  arc_cap_color: string
+  font_family: string
+}
+
+interface FilterConfig {
+  is_list_ignored: boolean
+  list: Array<string>
+  regex: boolean
+  case_sensitive: boolean
+  whole_word: boolean
+}
+
+interface Config {
+  appearance: AppearanceConfig
+  collection: {
+    show_average_cpu: boolean
+    use_current_cpu_total: boolean
+    unnormalized_cpu: boolean
+    count_open_files: boolean
+    proportional_memory_processes: number
+    // A duration like "3s", `true` for the default interval, or `false` to turn it off.
+    intervals: Partial<Record<Subsystem, string | boolean>>
+  }
+  filters: {
+    disk: FilterConfig | null
+    mount: FilterConfig | null
+    temperature: FilterConfig | null
+  }
+  exporter: {
+    enabled: boolean
+    address: string
+    include_processes: boolean
+  }
+  history: {
+    persist: boolean
+    // A duration like "7days".
+    max_age: string
+    max_size_mib: number
+  }
+  logging: {
+    level: "off" | "error" | "warn" | "info" | "debug" | "trace"
+  }
+  alerts: Array<{
+    name: string
+    when: Array<{
+      metric: string
+      labels: Record<string, string>
+      above: number | null
+      below: number | null
+      clear: number | null
+    }>
+    for: string
+    cooldown: string
+    notify: boolean
+  }>
+}
+
+interface ConfigFileError {
+  message: string
+  line: number | null
+  column: number | null
+}
+
+type ConfigChanged = { config: Config } | { error: ConfigFileError }
+
+interface HistoryQuery {
+  // The spacing of the points, in milliseconds.
+  resolution: number
+  series: Array<{
+    labels: Record<string, string>
+    points: Array<{ time: number; value: number }>
+  }>
+}
+
+interface ReplayStatus {
+  path: string
+  // The index of the snapshot last shown.
+  position: number
+  length: number
+  // When the snapshot last shown was recorded, in milliseconds since the Unix epoch.
+  time: number
+  paused: boolean
+  speed: number
+}
+
+interface AlertEvent {
+  rule: string
+  kind: "firing" | "resolved"
+  // In milliseconds since the Unix epoch.
+  time: number
+  breaches: Array<{ series: string; value: number }>
+}
+
+// The payload of the `processEvent` event, and what `get_process_events` returns.
+interface ProcessEvent {
+  kind: "started" | "exited"
+  // When the process list that showed it was collected, in milliseconds since the Unix epoch.
+  time: number
+  pid: string
+  name: string
+  command: string
+  user: string | null
+  start_time: number
+  // Only for exits.
+  lifetime_millis: number | null
+  peak_cpu_usage_percent: number
+  peak_mem_usage_bytes: number
 }